@@ -1,7 +1,11 @@
-use crate::{human_readable_folder_size, Message, PathItem, PathState};
+use crate::{
+    lazy_size, remove_path, Config, Message, PathItem, PathState, SizeUnits, TruncateStyle,
+};
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -10,15 +14,20 @@ use ratatui::{
     layout::{Alignment, Constraint, Layout, Rect},
     style::{Color, Modifier, Style, Styled, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, Padding, Paragraph, Row, Table, TableState},
+    widgets::{Block, Borders, Clear, Padding, Paragraph, Row, Table, TableState},
     Frame, Terminal,
 };
-use remove_dir_all::remove_dir_all;
 use std::{
+    collections::HashSet,
     io::{self, stdout},
+    panic,
     path::{Path, PathBuf},
-    sync::mpsc::{Receiver, Sender},
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use threadpool::ThreadPool;
 
@@ -26,8 +35,38 @@ use threadpool::ThreadPool;
 const PATH_PRESERVE_WIDTH: usize = 12;
 /// interval to refresh ui
 const TICK_INTERVAL: u64 = 100;
+/// items modified within these days are considered recent
+const AGE_RECENT_DAYS: u64 = 7;
+/// items not modified within these days are considered stale
+const AGE_STALE_DAYS: u64 = 30;
+/// max width of the rule tags, without the brackets
+const RULE_TAG_WIDTH: usize = 8;
+/// colors of the rule tags, picked by the hash of the rule id
+const RULE_TAG_COLORS: [Color; 6] = [
+    Color::Blue,
+    Color::Magenta,
+    Color::Green,
+    Color::Cyan,
+    Color::Yellow,
+    Color::LightRed,
+];
+/// commands of the command line
+const COMMANDS: [&str; 12] = [
+    "sort path|time|size",
+    "filter [TEXT]",
+    "mark",
+    "invert",
+    "delete",
+    "delete-marked",
+    "delete-all",
+    "preview",
+    "confirm",
+    "pause",
+    "help",
+    "quit",
+];
 /// spinner dots
-const SPINNER_DOTS: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+pub const SPINNER_DOTS: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
 #[derive(Debug, Default)]
 struct App {
@@ -39,6 +78,54 @@ struct App {
     error: Option<String>,
     app_state: AppState,
     pool: ThreadPool,
+    no_wrap: bool,
+    age_color: bool,
+    /// move deleted items out of the list into the deleted panel
+    group_deleted: bool,
+    deleted_items: Vec<PathItem>,
+    show_deleted: bool,
+    show_help: bool,
+    /// width of the size column
+    size_width: u16,
+    /// units the sizes are formatted with
+    units: SizeUnits,
+    /// backend and safety checks of the deletions
+    delete_config: Arc<Config>,
+    /// part of long paths to cut
+    truncate: TruncateStyle,
+    /// stop accepting new items once reached
+    max_items: Option<usize>,
+    /// items marked for deletion
+    marked: HashSet<PathBuf>,
+    /// sizes are not computed, so items without a size can be deleted
+    no_size: bool,
+    /// sizes are computed once the items are shown
+    lazy_size: bool,
+    /// items whose size is being computed, used with `lazy_size`
+    sizing: HashSet<PathBuf>,
+    no_hardlink_dedup: bool,
+    /// without colors, the selected row is shown reversed
+    color: bool,
+    /// area of the item list in the last frame, used to map clicks to rows
+    table_area: Rect,
+    /// shared with the search, which waits while it is set
+    paused: Arc<AtomicBool>,
+    /// deletions are only planned until confirmed
+    preview: bool,
+    /// prefix the rows with the rule they were found by, set when several rules are active
+    rule_tags: bool,
+    /// file to save the items to on exit
+    save_session: Option<PathBuf>,
+    /// input of the command line, open while set
+    command: Option<String>,
+    /// only the items whose path contains the text are listed
+    filter: Option<String>,
+    /// items hidden by the filter
+    filtered_out: Vec<PathItem>,
+    /// when the search started
+    started: Option<Instant>,
+    /// duration of the search, frozen once done
+    elapsed: Option<Duration>,
 }
 
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
@@ -49,23 +136,72 @@ enum AppState {
     Exit,
 }
 
-pub fn run(rx: Receiver<Message>, tx: Sender<Message>) -> io::Result<()> {
-    let mut terminal = init_terminal()?;
+pub fn run(
+    rx: Receiver<Message>,
+    tx: Sender<Message>,
+    config: &Config,
+    paused: Arc<AtomicBool>,
+) -> io::Result<()> {
+    // leave raw mode and the alternate screen before the panic message is printed
+    let prev_hook = Arc::new(panic::take_hook());
+    let hook = prev_hook.clone();
+    let mouse = config.mouse;
+    panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        if mouse {
+            let _ = stdout().execute(DisableMouseCapture);
+        }
+        let _ = stdout().execute(LeaveAlternateScreen);
+        hook(info);
+    }));
+    let mut app = App {
+        no_wrap: config.no_wrap,
+        age_color: config.age_color,
+        group_deleted: config.group_deleted,
+        size_width: config.units.width(),
+        units: config.units,
+        delete_config: Arc::new(config.clone()),
+        truncate: config.truncate,
+        max_items: config.max_items,
+        no_size: config.no_size || config.lazy_size,
+        lazy_size: config.lazy_size,
+        rule_tags: config.rules.len() > 1,
+        no_hardlink_dedup: config.no_hardlink_dedup,
+        color: config.color.enabled(true),
+        paused,
+        started: Some(Instant::now()),
+        save_session: config.save_session.clone(),
+        ..Default::default()
+    };
+    if let Some(file) = &config.load_session {
+        app.load_session(file)?;
+    }
+    let mut terminal = init_terminal(mouse)?;
     // result is evaluated after restoring terminal to ensure that it does not get printed on the
     // alternate screen in raw mode
-    let res = App::default().run(&mut terminal, tx, rx);
-    restore_terminal(terminal)?;
+    let res = app.run(&mut terminal, tx, rx);
+    drop(panic::take_hook());
+    if let Ok(prev_hook) = Arc::try_unwrap(prev_hook) {
+        panic::set_hook(prev_hook);
+    }
+    restore_terminal(terminal, mouse)?;
     res
 }
 
-fn init_terminal() -> io::Result<Terminal<impl Backend>> {
+fn init_terminal(mouse: bool) -> io::Result<Terminal<impl Backend>> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
+    if mouse {
+        stdout().execute(EnableMouseCapture)?;
+    }
     Terminal::new(CrosstermBackend::new(stdout()))
 }
 
-fn restore_terminal(mut terminal: Terminal<impl Backend>) -> io::Result<()> {
+fn restore_terminal(mut terminal: Terminal<impl Backend>, mouse: bool) -> io::Result<()> {
     disable_raw_mode()?;
+    if mouse {
+        stdout().execute(DisableMouseCapture)?;
+    }
     stdout().execute(LeaveAlternateScreen)?;
     terminal.show_cursor()?;
     Ok(())
@@ -82,6 +218,9 @@ impl App {
         let mut last_tick = Instant::now();
         while self.app_state != AppState::Exit {
             terminal.draw(|frame| self.draw(frame))?;
+            if self.lazy_size {
+                self.size_visible_items(&tx);
+            }
 
             self.handle_next_message(&rx);
 
@@ -96,6 +235,114 @@ impl App {
                 last_tick = Instant::now();
             }
         }
+        match &self.save_session {
+            Some(file) => self.write_session(file),
+            None => Ok(()),
+        }
+    }
+
+    /// write a `root\t<root>` line per search root, then the remaining items, one
+    /// `<state>\t<size>\t<mtime>\t<rule>\t<relative path>\t<path>` line each, `-` stands for an
+    /// unknown size or mtime
+    fn write_session(&self, file: &Path) -> io::Result<()> {
+        let now = SystemTime::now();
+        let mut output = String::new();
+        for root in &self.delete_config.scan_roots {
+            output.push_str(&format!("root\t{}\n", root.display()));
+        }
+        for item in &self.items {
+            if matches!(item.state, PathState::Deleted) {
+                continue;
+            }
+            let state = if self.marked.contains(&item.path) {
+                "marked"
+            } else {
+                "normal"
+            };
+            let size = item.size.map(|v| v.to_string());
+            let mtime = item
+                .time
+                .and_then(|v| now.checked_sub(v))
+                .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+                .map(|v| v.as_secs().to_string());
+            output.push_str(&format!(
+                "{state}\t{}\t{}\t{}\t{}\t{}\n",
+                size.as_deref().unwrap_or("-"),
+                mtime.as_deref().unwrap_or("-"),
+                item.rule_id,
+                item.relative_path.display(),
+                item.path.display()
+            ));
+        }
+        std::fs::write(file, output)
+    }
+
+    /// restore the items of a saved session instead of searching, the vanished ones are dropped,
+    /// deletions are confined to the roots it records
+    fn load_session(&mut self, file: &Path) -> io::Result<()> {
+        let content = std::fs::read_to_string(file).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Failed to read session from '{}', {}", file.display(), err),
+            )
+        })?;
+        let now = SystemTime::now();
+        let mut vanished = 0;
+        let mut config = (*self.delete_config).clone();
+        for line in content.lines() {
+            if let Some(root) = line.strip_prefix("root\t") {
+                config.confine_to_root(Path::new(root));
+                continue;
+            }
+            let invalid = || io::Error::other(format!("Invalid session line '{}'", line));
+            let fields: Vec<&str> = line.splitn(6, '\t').collect();
+            let [state, size, mtime, rule_id, relative_path, path] = fields[..] else {
+                return Err(invalid());
+            };
+            let parse = |v: &str| match v {
+                "-" => Ok(None),
+                v => v.parse::<u64>().map(Some).map_err(|_| invalid()),
+            };
+            let size = parse(size)?;
+            let time = parse(mtime)?
+                .and_then(|v| now.duration_since(UNIX_EPOCH + Duration::from_secs(v)).ok());
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                vanished += 1;
+                continue;
+            }
+            match state {
+                "marked" => {
+                    self.marked.insert(path.clone());
+                }
+                "normal" => {}
+                _ => return Err(invalid()),
+            }
+            let item = PathItem::new(
+                path,
+                PathBuf::from(relative_path),
+                rule_id,
+                time,
+                size,
+                self.units,
+            );
+            self.total_size += item.size.unwrap_or_default();
+            self.add_item(item);
+        }
+        if config.scan_roots.is_empty() {
+            return Err(io::Error::other(format!(
+                "Session '{}' has no search root, refusing to load it",
+                file.display()
+            )));
+        }
+        self.delete_config = Arc::new(config);
+        if vanished > 0 {
+            self.error = Some(format!(
+                "Dropped {vanished} saved targets that no longer exist"
+            ));
+        }
+        self.app_state = AppState::SearchingDone;
+        self.elapsed = Some(Duration::ZERO);
         Ok(())
     }
 
@@ -103,26 +350,51 @@ impl App {
         let Ok(item) = rx.try_recv() else { return };
         match item {
             Message::AddPath(item) => {
+                if self
+                    .max_items
+                    .map(|v| self.items.len() >= v)
+                    .unwrap_or_default()
+                {
+                    self.error = Some(format!("Results truncated at {} items", self.items.len()));
+                    return;
+                }
                 self.total_size += item.size.unwrap_or_default();
                 self.add_item(item);
             }
             Message::DoneSearch => {
                 self.app_state = AppState::SearchingDone;
+                self.elapsed = self.started.map(|v| v.elapsed());
             }
             Message::SetPathDeleted(path) => {
                 let size = self.set_item_deleted(path);
                 self.total_saved_size += size.unwrap_or_default();
             }
+            Message::SetPathFailed(path, message) => {
+                self.set_item_failed(path);
+                self.error = Some(message);
+            }
+            Message::SetPathSize(path, size) => self.set_item_size(path, size),
             Message::PutError(message) => {
                 self.error = Some(message);
             }
+            Message::AddUnknown(_) => {}
+            Message::SyncPaths(items) => self.sync_items(items),
         }
     }
 
     fn handle_events(&mut self, timeout: Duration, tx: &Sender<Message>) -> Result<(), io::Error> {
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                self.handle_key_event(key, tx)?;
+            match event::read()? {
+                Event::Key(key) => self.handle_key_event(key, tx)?,
+                Event::Mouse(mouse) if !self.show_help => match mouse.kind {
+                    MouseEventKind::ScrollDown => self.scroll(1),
+                    MouseEventKind::ScrollUp => self.scroll(-1),
+                    MouseEventKind::Down(event::MouseButton::Left) => {
+                        self.click(mouse.column, mouse.row)
+                    }
+                    _ => {}
+                },
+                _ => {}
             }
         }
         Ok(())
@@ -134,7 +406,40 @@ impl App {
             return Ok(());
         }
         self.clear_tmp_state();
+        if let Some(command) = self.command.as_mut() {
+            match key.code {
+                KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+                    self.app_state = AppState::Exit;
+                }
+                KeyCode::Char(c) => command.push(c),
+                KeyCode::Backspace if command.is_empty() => self.command = None,
+                KeyCode::Backspace => {
+                    command.pop();
+                }
+                KeyCode::Enter => {
+                    let command = self.command.take().unwrap_or_default();
+                    if let Err(err) = self.run_command(&command, tx) {
+                        self.error = Some(err);
+                    }
+                }
+                KeyCode::Esc => self.command = None,
+                _ => {}
+            }
+            return Ok(());
+        }
+        if self.show_help {
+            match key.code {
+                KeyCode::Char('?') | KeyCode::Esc => self.show_help = false,
+                KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+                    self.app_state = AppState::Exit;
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
         match key.code {
+            KeyCode::Char('?') => self.show_help = true,
+            KeyCode::Char(':') => self.command = Some(String::new()),
             KeyCode::Down => {
                 if key.kind == event::KeyEventKind::Press {
                     self.next()
@@ -148,8 +453,15 @@ impl App {
             KeyCode::Char(' ') => {
                 self.delete_item(tx.clone());
             }
+            KeyCode::Char('p') if self.app_state == AppState::Searching => self.toggle_pause(),
+            KeyCode::Char('v') => self.toggle_preview(),
+            KeyCode::Enter if self.preview => self.confirm_pending(tx.clone()),
+            KeyCode::Char('m') => self.toggle_mark(),
+            KeyCode::Char('i') => self.invert_marks(),
+            KeyCode::Char('d') => self.delete_marked_items(tx.clone()),
             KeyCode::Home => self.begin(),
             KeyCode::End => self.end(),
+            KeyCode::F(2) if self.group_deleted => self.show_deleted = !self.show_deleted,
             KeyCode::F(4) => self.delete_all_items(tx.clone()),
             KeyCode::F(7) => self.order_by_path(),
             KeyCode::F(8) => self.order_by_lastmod(),
@@ -166,35 +478,89 @@ impl App {
     }
 
     fn draw(&mut self, frame: &mut Frame) {
-        let mut constraints = vec![Constraint::Min(0), Constraint::Length(1)];
+        let deleted_height = match (self.group_deleted, self.show_deleted) {
+            (false, _) => 0,
+            (true, false) => 1,
+            (true, true) => (self.deleted_items.len() as u16 + 2).min(frame.size().height / 3),
+        };
+        let mut constraints = vec![
+            Constraint::Min(0),
+            Constraint::Length(deleted_height),
+            Constraint::Length(1),
+        ];
         if self.error.is_some() {
             constraints.push(Constraint::Length(1));
         };
+        if self.command.is_some() {
+            constraints.push(Constraint::Length(1));
+        }
 
         let areas = Layout::default()
             .constraints(constraints)
             .split(frame.size());
 
         self.draw_table_view(frame, areas[0]);
-        self.draw_status_bar(frame, areas[1]);
+        if self.group_deleted {
+            self.draw_deleted_view(frame, areas[1]);
+        }
+        self.draw_status_bar(frame, areas[2]);
         if let Some(error) = self.error.as_ref() {
-            Self::draw_error_line(frame, error, areas[2])
+            Self::draw_error_line(frame, error, areas[3])
+        }
+        if let Some(command) = self.command.as_ref() {
+            Self::draw_command_line(frame, command, areas[areas.len() - 1]);
+        }
+        if self.show_help {
+            self.draw_help(frame);
+        }
+        if !self.color {
+            for cell in frame.buffer_mut().content.iter_mut() {
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+            }
         }
     }
 
     fn draw_table_view(&mut self, frame: &mut Frame, area: Rect) {
-        let path_width = area.width - 17; // 2(border) + 1(padding) + 3(gap) + 1(indicator) + 5(day) + 5(size)
-        let widths = [
+        self.table_area = area;
+        // 2(border) + 1(padding) + 3(gap) + 1(indicator) + 5(day) + size
+        let mut path_width = area.width.saturating_sub(12 + self.size_width);
+        let tag_width = (RULE_TAG_WIDTH + 2) as u16;
+        if self.rule_tags {
+            path_width = path_width.saturating_sub(tag_width + 1);
+        }
+        let mut widths = vec![
             Constraint::Length(1),
             Constraint::Length(path_width),
             Constraint::Length(5),
-            Constraint::Length(5),
+            Constraint::Length(self.size_width),
         ];
-        let rows = self.items.iter().enumerate().map(|(index, item)| {
-            let is_selected = self.table_state.selected() == Some(index);
+        if self.rule_tags {
+            widths.insert(1, Constraint::Length(tag_width));
+        }
+        // only the visible rows are built, the table is rendered with a state relative to them
+        let height = area.height.saturating_sub(2) as usize;
+        let selected = self.table_state.selected();
+        let mut offset = self.table_state.offset();
+        if let Some(selected) = selected {
+            if selected < offset {
+                offset = selected;
+            } else if selected >= offset + height {
+                offset = selected + 1 - height;
+            }
+        }
+        offset = offset.min(self.items.len().saturating_sub(height));
+        *self.table_state.offset_mut() = offset;
+        let visible = &self.items[offset..(offset + height).min(self.items.len())];
+        let rows = visible.iter().enumerate().map(|(index, item)| {
+            let is_selected = selected == Some(offset + index);
             let mut style = Style::default();
             if is_selected {
-                style = style.fg(Color::Cyan);
+                style = if self.color {
+                    style.fg(Color::Cyan)
+                } else {
+                    style.add_modifier(Modifier::REVERSED)
+                };
             }
             let indicator = match item.state {
                 PathState::Deleted => {
@@ -202,63 +568,215 @@ impl App {
                     "✘".to_string()
                 }
                 PathState::StartDeleting => self.spinner().to_string(),
+                PathState::PendingDelete => {
+                    style = style.fg(Color::Red);
+                    "◌".to_string()
+                }
+                _ if self.marked.contains(&item.path) => {
+                    style = style.fg(Color::Yellow);
+                    "●".to_string()
+                }
+                _ if item.symlink => "↪".to_string(),
                 _ => String::new(),
             };
-            let row_cells = [
-                (indicator, Alignment::Left),
+            let path_style = if item.archived {
+                style.add_modifier(Modifier::ITALIC)
+            } else {
+                style
+            };
+            let mut time_style = style;
+            if self.age_color && !is_selected && item.state == PathState::Normal {
+                if let Some(color) = age_color(item.time) {
+                    time_style = time_style.fg(color);
+                }
+            }
+            let mut row_cells = vec![
+                (indicator, Alignment::Left, style),
                 (
-                    truncate_path(&item.relative_path, path_width),
+                    truncate_path(&item.relative_path, path_width, self.truncate),
                     Alignment::Left,
+                    path_style,
                 ),
-                (item.time_text.clone(), Alignment::Right),
-                (item.size_text.clone(), Alignment::Right),
-            ]
-            .into_iter()
-            .map(|(t, a)| Line::from(vec![t.set_style(style)]).alignment(a));
+                (item.time_text.clone(), Alignment::Right, time_style),
+                (item.size_text.clone(), Alignment::Right, style),
+            ];
+            if self.rule_tags {
+                let tag_style = if is_selected {
+                    style
+                } else {
+                    style.fg(rule_color(&item.rule_id))
+                };
+                let tag = format!("[{}]", rule_tag(&item.rule_name));
+                row_cells.insert(1, (tag, Alignment::Left, tag_style));
+            }
+            let row_cells = row_cells
+                .into_iter()
+                .map(|(t, a, s)| Line::from(vec![t.set_style(s)]).alignment(a));
             Row::new(row_cells)
         });
         let table = Table::new(rows, widths).column_spacing(1).block(
             Block::default()
                 .borders(Borders::ALL)
                 .padding(Padding::new(0, 1, 0, 0))
-                .title(Self::title_line()),
+                .title(self.title_line()),
         );
 
-        frame.render_stateful_widget(table, area, &mut self.table_state);
+        let mut state = TableState::default().with_selected(selected.map(|v| v - offset));
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+
+    fn draw_deleted_view(&mut self, frame: &mut Frame, area: Rect) {
+        let title = format!(" Deleted ({}) ", self.deleted_items.len());
+        if !self.show_deleted {
+            let line = Line::from(vec![title.dark_gray(), "F2 to expand".dark_gray()]);
+            frame.render_widget(Paragraph::new(line), area);
+            return;
+        }
+        let path_width = area.width.saturating_sub(4 + self.size_width); // 2(border) + 1(padding) + 1(gap) + size
+        let widths = [
+            Constraint::Length(path_width),
+            Constraint::Length(self.size_width),
+        ];
+        let rows = self.deleted_items.iter().map(|item| {
+            let style = Style::default().add_modifier(Modifier::DIM);
+            Row::new([
+                Line::from(vec![truncate_path(
+                    &item.relative_path,
+                    path_width,
+                    self.truncate,
+                )
+                .set_style(style)]),
+                Line::from(vec![item.size_text.clone().set_style(style)])
+                    .alignment(Alignment::Right),
+            ])
+        });
+        let table = Table::new(rows, widths).column_spacing(1).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .padding(Padding::new(0, 1, 0, 0))
+                .title(title),
+        );
+        frame.render_widget(table, area);
+    }
+
+    fn draw_help(&self, frame: &mut Frame) {
+        let mut keybindings = vec![
+            ("↑/↓", "Move selection"),
+            ("Home/End", "Move to the first/last item"),
+            ("SPACE", "Delete the selected item"),
+            ("p", "Pause/resume the search"),
+            ("v", "Toggle the preview, deletions are only planned"),
+            ("ENTER", "Delete the planned items in the preview"),
+            ("m", "Mark/unmark the selected item"),
+            ("i", "Invert the marks"),
+            ("d", "Delete the marked items"),
+            ("F4", "Delete all items"),
+            ("F7", "Sort by path"),
+            ("F8", "Sort by last modified time"),
+            ("F9", "Sort by size"),
+            (":", "Run a command, e.g. sort size, filter node"),
+            ("?", "Toggle this help"),
+            ("ESC/Ctrl-C", "Exit"),
+        ];
+        if self.group_deleted {
+            keybindings.insert(3, ("F2", "Toggle the deleted panel"));
+        }
+        let lines: Vec<Line> = keybindings
+            .into_iter()
+            .map(|(k, v)| Line::from(vec![format!("{k:>12}  ").yellow(), v.into()]))
+            .collect();
+        let size = frame.size();
+        let width = 50.min(size.width);
+        let height = (lines.len() as u16 + 2).min(size.height);
+        let area = Rect::new(
+            (size.width - width) / 2,
+            (size.height - height) / 2,
+            width,
+            height,
+        );
+        let help = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Help (? or ESC to close) "),
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(help, area);
     }
 
     fn draw_status_bar(&mut self, frame: &mut Frame, area: Rect) {
         let search_indicator = match self.app_state {
+            AppState::Searching if self.is_paused() => " paused ".to_string(),
             AppState::Searching => format!(" {} ", self.spinner()),
             AppState::SearchingDone => " ✔ ".to_string(),
             AppState::Exit => " ✘ ".to_string(),
         };
 
-        let status_line = Line::from(vec![
+        let mut spans = vec![
             search_indicator.into(),
+            format!("{}s ", self.elapsed().as_secs()).dark_gray(),
             "total space: ".dark_gray(),
-            human_readable_folder_size(self.total_size).into(),
+            self.units.format(self.total_size).into(),
             " released space:".dark_gray(),
-            human_readable_folder_size(self.total_saved_size).into(),
-            " ".into(),
-        ]);
+            self.units.format(self.total_saved_size).into(),
+            format!(
+                " ({}%)",
+                freed_percent(self.total_saved_size, self.total_size)
+            )
+            .dark_gray(),
+            " remaining:".dark_gray(),
+            self.units
+                .format(self.total_size.saturating_sub(self.total_saved_size))
+                .into(),
+        ];
+        if self.preview {
+            let pending: u64 = self
+                .items
+                .iter()
+                .filter(|v| v.state == PathState::PendingDelete)
+                .map(|v| v.size.unwrap_or_default())
+                .sum();
+            spans.push(" preview, would release:".red());
+            spans.push(self.units.format(pending).into());
+        }
+        if let Some(filter) = &self.filter {
+            spans.push(" filter:".dark_gray());
+            spans.push(filter.clone().yellow());
+        }
+        if !self.marked.is_empty() {
+            spans.push(" marked:".dark_gray());
+            spans.push(self.marked.len().to_string().yellow());
+        }
+        spans.push(" ".into());
+        spans.push(self.pagination().dark_gray());
+        let status_line = Line::from(spans);
 
         frame.render_widget(Paragraph::new(status_line), area);
     }
 
+    fn draw_command_line(frame: &mut Frame, command: &str, area: Rect) {
+        let line = Line::from(vec![":".yellow(), command.to_string().into()]);
+        frame.render_widget(Paragraph::new(line), area);
+        let x = area.x + 1 + command.chars().count() as u16;
+        frame.set_cursor(x.min(area.right().saturating_sub(1)), area.y);
+    }
+
     fn draw_error_line(frame: &mut Frame, error: &str, area: Rect) {
         let error_line = error.to_string().red();
         frame.render_widget(Paragraph::new(error_line), area);
     }
 
-    fn title_line() -> Line<'static> {
-        let hotkeys = vec![
+    fn title_line(&self) -> Line<'static> {
+        let mut hotkeys = vec![
             ("↑↓", "Move"),
             ("SPACE", "Delete"),
             ("F4", "Delete All"),
             ("F7/F8/F9", "Sort by Path/LastMod/Size"),
+            ("?", "Help"),
             ("ESC", "Exit"),
         ];
+        if self.group_deleted {
+            hotkeys.insert(3, ("F2", "Toggle Deleted"));
+        }
         let colors = [
             Style::default().fg(Color::Yellow),
             Style::default().fg(Color::DarkGray),
@@ -279,26 +797,83 @@ impl App {
 }
 
 impl App {
-    /// move selection to next item (with wrap around to the top)
+    /// move selection to next item (with wrap around to the top unless no_wrap)
     fn next(&mut self) {
+        let len = self.items.len();
+        if len == 0 {
+            return;
+        }
         let next = self
             .table_state
             .selected()
-            .map(|i| (i + 1) % self.items.len())
+            .map(|i| {
+                if self.no_wrap {
+                    (i + 1).min(len.saturating_sub(1))
+                } else {
+                    (i + 1) % len
+                }
+            })
             .or(Some(0));
         self.table_state.select(next);
     }
 
-    /// select the previous item (with wrap around to the bottom)
+    /// select the previous item (with wrap around to the bottom unless no_wrap)
     fn previous(&mut self) {
+        let len = self.items.len();
+        if len == 0 {
+            return;
+        }
         let next = self
             .table_state
             .selected()
-            .map(|i| (i + self.items.len().saturating_sub(1)) % self.items.len())
+            .map(|i| {
+                if self.no_wrap {
+                    i.saturating_sub(1)
+                } else {
+                    (i + len.saturating_sub(1)) % len
+                }
+            })
             .or(Some(0));
         self.table_state.select(next);
     }
 
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused.store(!self.is_paused(), Ordering::SeqCst);
+    }
+
+    /// move the selection by the scrolled rows without wrapping around
+    fn scroll(&mut self, delta: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let selected = self.table_state.selected().unwrap_or_default();
+        let next = selected
+            .saturating_add_signed(delta)
+            .min(self.items.len() - 1);
+        self.table_state.select(Some(next));
+    }
+
+    /// select the row under the clicked cell of the list
+    fn click(&mut self, column: u16, row: u16) {
+        let area = self.table_area;
+        // rows start below the top border
+        if column <= area.x
+            || column >= area.right().saturating_sub(1)
+            || row <= area.y
+            || row >= area.bottom().saturating_sub(1)
+        {
+            return;
+        }
+        let index = self.table_state.offset() + (row - area.y - 1) as usize;
+        if index < self.items.len() {
+            self.table_state.select(Some(index));
+        }
+    }
+
     /// move selection to the top
     fn begin(&mut self) {
         if self.items.is_empty() {
@@ -331,45 +906,295 @@ impl App {
     }
 
     fn add_item(&mut self, item: PathItem) {
-        self.items.push(item);
+        if self.is_filtered_out(&item) {
+            self.filtered_out.push(item);
+        } else {
+            self.items.push(item);
+        }
+    }
+
+    /// run a line of the command line, see `COMMANDS`
+    fn run_command(&mut self, command: &str, tx: &Sender<Message>) -> Result<(), String> {
+        let (name, arg) = match command.trim().split_once(' ') {
+            Some((name, arg)) => (name, Some(arg.trim()).filter(|v| !v.is_empty())),
+            None => (command.trim(), None),
+        };
+        match (name, arg) {
+            ("", None) => {}
+            ("sort", Some("path")) => self.order_by_path(),
+            ("sort", Some("time")) => self.order_by_lastmod(),
+            ("sort", Some("size")) => self.order_by_size(),
+            ("sort", _) => return Err("Usage: sort path|time|size".into()),
+            ("filter", text) => self.set_filter(text.map(String::from)),
+            ("mark", None) => self.toggle_mark(),
+            ("invert", None) => self.invert_marks(),
+            ("delete", None) => self.delete_item(tx.clone()),
+            ("delete-marked", None) => self.delete_marked_items(tx.clone()),
+            ("delete-all", None) => self.delete_all_items(tx.clone()),
+            ("preview", None) => self.toggle_preview(),
+            ("confirm", None) if self.preview => self.confirm_pending(tx.clone()),
+            ("pause", None) if self.app_state == AppState::Searching => self.toggle_pause(),
+            ("help", None) => self.show_help = true,
+            ("quit" | "q", None) => self.app_state = AppState::Exit,
+            _ => {
+                return Err(format!(
+                    "Unknown command '{}', available: {}",
+                    command.trim(),
+                    COMMANDS.join(", ")
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// list only the items whose relative path contains the text, all items without one
+    fn set_filter(&mut self, filter: Option<String>) {
+        self.filter = filter;
+        self.apply_filter();
+        let selected = (!self.items.is_empty()).then_some(0);
+        self.table_state.select(selected);
+    }
+
+    /// move the items in or out of view according to the filter
+    fn apply_filter(&mut self) {
+        let items = std::mem::take(&mut self.items);
+        let hidden = std::mem::take(&mut self.filtered_out);
+        for item in items.into_iter().chain(hidden) {
+            self.add_item(item);
+        }
+    }
+
+    /// only normal items are hidden, so deletions in progress stay visible
+    fn is_filtered_out(&self, item: &PathItem) -> bool {
+        let Some(filter) = &self.filter else {
+            return false;
+        };
+        item.state == PathState::Normal
+            && !item
+                .relative_path
+                .to_string_lossy()
+                .contains(filter.as_str())
+    }
+
+    /// merge in targets found by a re-scan and drop the vanished ones
+    fn sync_items(&mut self, items: Vec<PathItem>) {
+        // the hidden items are synced as well
+        self.items.append(&mut self.filtered_out);
+        self.sync_all_items(items);
+        self.apply_filter();
+        if let Some(selected) = self.table_state.selected() {
+            let selected = selected.min(self.items.len().saturating_sub(1));
+            self.table_state
+                .select((!self.items.is_empty()).then_some(selected));
+        }
+    }
+
+    fn sync_all_items(&mut self, items: Vec<PathItem>) {
+        let paths: HashSet<&PathBuf> = items.iter().map(|v| &v.path).collect();
+        let (vanished, kept): (Vec<PathItem>, Vec<PathItem>) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition(|item| {
+                matches!(item.state, PathState::Normal | PathState::PendingDelete)
+                    && !paths.contains(&item.path)
+            });
+        self.items = kept;
+        for item in vanished {
+            self.total_size -= item.size.unwrap_or_default();
+            self.marked.remove(&item.path);
+        }
+        let known: HashSet<PathBuf> = self
+            .items
+            .iter()
+            .chain(self.deleted_items.iter())
+            .map(|v| v.path.clone())
+            .collect();
+        for item in items {
+            if !known.contains(&item.path) {
+                self.total_size += item.size.unwrap_or_default();
+                self.add_item(item);
+            }
+        }
+        if let Some(selected) = self.table_state.selected() {
+            if self.items.is_empty() {
+                self.table_state.select(None);
+            } else if selected >= self.items.len() {
+                self.table_state.select(Some(self.items.len() - 1));
+            }
+        }
     }
 
     fn delete_item(&mut self, sender: Sender<Message>) {
-        if let Some(path) = self.start_deleting_item() {
-            spawn_delete_path(self.pool.clone(), path, sender);
+        let Some(index) = self.table_state.selected() else {
+            return;
+        };
+        let item = &mut self.items[index];
+        if item.state == PathState::PendingDelete {
+            item.state = PathState::Normal;
+        } else if is_deletable(item, self.no_size, self.delete_config.delete_symlinks) {
+            self.begin_delete(index, &sender);
         }
     }
 
     fn delete_all_items(&mut self, sender: Sender<Message>) {
+        for index in 0..self.items.len() {
+            if is_deletable(
+                &self.items[index],
+                self.no_size,
+                self.delete_config.delete_symlinks,
+            ) {
+                self.begin_delete(index, &sender);
+            }
+        }
+    }
+
+    /// start deleting the item, or only plan it in the preview
+    fn begin_delete(&mut self, index: usize, sender: &Sender<Message>) {
+        let item = &mut self.items[index];
+        if self.preview {
+            item.state = PathState::PendingDelete;
+            return;
+        }
+        item.state = PathState::StartDeleting;
+        spawn_delete_path(
+            self.pool.clone(),
+            item.all_paths(),
+            sender.clone(),
+            self.delete_config.clone(),
+        );
+    }
+
+    /// leaving the preview drops the planned deletions
+    fn toggle_preview(&mut self) {
+        self.preview = !self.preview;
+        if !self.preview {
+            for item in self.items.iter_mut() {
+                if item.state == PathState::PendingDelete {
+                    item.state = PathState::Normal;
+                }
+            }
+        }
+    }
+
+    fn confirm_pending(&mut self, sender: Sender<Message>) {
         for item in self.items.iter_mut() {
-            if item.state == PathState::Normal && item.size.is_some() {
+            if item.state == PathState::PendingDelete {
                 item.state = PathState::StartDeleting;
-                spawn_delete_path(self.pool.clone(), item.path.clone(), sender.clone());
+                spawn_delete_path(
+                    self.pool.clone(),
+                    item.all_paths(),
+                    sender.clone(),
+                    self.delete_config.clone(),
+                );
             }
         }
     }
 
-    fn start_deleting_item(&mut self) -> Option<PathBuf> {
-        if let Some(index) = self.table_state.selected() {
-            let item = &mut self.items[index];
-            if item.state != PathState::Normal || item.size.is_none() {
-                None
-            } else {
-                item.state = PathState::StartDeleting;
-                Some(item.path.clone())
+    /// mark or unmark the selected item
+    fn toggle_mark(&mut self) {
+        let Some(item) = self.table_state.selected().map(|i| &self.items[i]) else {
+            return;
+        };
+        if item.state != PathState::Normal {
+            return;
+        }
+        if !self.marked.remove(&item.path) {
+            self.marked.insert(item.path.clone());
+        }
+    }
+
+    /// mark the unmarked items and unmark the marked ones, the marks of the items hidden by the
+    /// filter are kept
+    fn invert_marks(&mut self) {
+        for item in &self.items {
+            if !self.marked.remove(&item.path) && item.state == PathState::Normal {
+                self.marked.insert(item.path.clone());
             }
-        } else {
-            None
         }
     }
 
-    fn set_item_deleted(&mut self, path: PathBuf) -> Option<u64> {
+    fn delete_marked_items(&mut self, sender: Sender<Message>) {
+        for index in 0..self.items.len() {
+            let item = &self.items[index];
+            if is_deletable(item, self.no_size, self.delete_config.delete_symlinks)
+                && self.marked.remove(&item.path)
+            {
+                self.begin_delete(index, &sender);
+            }
+        }
+    }
+
+    /// reset the item of a failed deletion, so it can be deleted again
+    fn set_item_failed(&mut self, path: PathBuf) {
         if let Some(item) = self.items.iter_mut().find(|item| item.path == path) {
-            item.state = PathState::Deleted;
-            item.size
-        } else {
-            None
+            item.state = PathState::Normal;
+        }
+    }
+
+    fn set_item_deleted(&mut self, path: PathBuf) -> Option<u64> {
+        let index = self.items.iter().position(|item| item.path == path)?;
+        let item = &mut self.items[index];
+        item.state = PathState::Deleted;
+        self.marked.remove(&item.path);
+        let size = item.size;
+        if self.group_deleted {
+            let item = self.items.remove(index);
+            self.deleted_items.push(item);
+            if let Some(selected) = self.table_state.selected() {
+                if self.items.is_empty() {
+                    self.table_state.select(None);
+                } else if selected > index || selected == self.items.len() {
+                    self.table_state.select(Some(selected - 1));
+                }
+            }
         }
+        size
+    }
+
+    /// compute the sizes of the items in the viewport that are not sized yet
+    fn size_visible_items(&mut self, sender: &Sender<Message>) {
+        let offset = self.table_state.offset();
+        let height = self.table_area.height.saturating_sub(2) as usize;
+        let end = (offset + height).min(self.items.len());
+        for item in self.items[offset.min(end)..end].iter() {
+            if item.size.is_some()
+                || item.state != PathState::Normal
+                || !self.sizing.insert(item.path.clone())
+            {
+                continue;
+            }
+            let paths = item.all_paths();
+            let sender = sender.clone();
+            let dedup = !self.no_hardlink_dedup;
+            self.pool.execute(move || {
+                let size = paths.iter().filter_map(|v| lazy_size(v, dedup)).sum();
+                let _ = sender.send(Message::SetPathSize(paths[0].clone(), size));
+            });
+        }
+    }
+
+    fn set_item_size(&mut self, path: PathBuf, size: u64) {
+        let Some(item) = self.items.iter_mut().find(|item| item.path == path) else {
+            return;
+        };
+        item.size = Some(size);
+        item.size_text = item.units.format(size);
+        self.total_size += size;
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.elapsed
+            .or_else(|| self.started.map(|v| v.elapsed()))
+            .unwrap_or_default()
+    }
+
+    /// position of the selected item, e.g. `12/340`
+    fn pagination(&self) -> String {
+        let index = self
+            .table_state
+            .selected()
+            .map(|v| v + 1)
+            .unwrap_or_default();
+        format!("{}/{}", index, self.items.len())
     }
 
     fn spinner(&self) -> &'static str {
@@ -387,31 +1212,98 @@ impl App {
     }
 }
 
-fn truncate_path(path: &Path, width: u16) -> String {
+/// share of the found size that has been released, rounded down
+fn freed_percent(saved: u64, total: u64) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+    (saved as u128 * 100 / total as u128).min(100) as u64
+}
+
+/// a normal item whose size is known, the size is missing when it could not be read, symlinks
+/// only with `delete_symlinks`
+fn is_deletable(item: &PathItem, no_size: bool, delete_symlinks: bool) -> bool {
+    item.state == PathState::Normal
+        && (no_size || item.size.is_some())
+        && (delete_symlinks || !item.symlink)
+}
+
+/// short name of the rule, its first target or marker for a custom rule, e.g. `target` for
+/// `target@Cargo.toml`
+fn rule_tag(rule_name: &str) -> String {
+    let name = match rule_name.split_once('@') {
+        Some(("", markers)) => markers,
+        Some((targets, _)) => targets,
+        None => rule_name,
+    };
+    let name = name.split(',').next().unwrap_or_default();
+    name.chars().take(RULE_TAG_WIDTH).collect()
+}
+
+/// a stable color for the rule
+fn rule_color(rule_id: &str) -> Color {
+    let hash = rule_id.bytes().fold(0usize, |hash, v| {
+        hash.wrapping_mul(31).wrapping_add(v as usize)
+    });
+    RULE_TAG_COLORS[hash % RULE_TAG_COLORS.len()]
+}
+
+/// color of the last modified time, recent items are green and stale items are gray
+fn age_color(time: Option<Duration>) -> Option<Color> {
+    let days = time?.as_secs() / 86400;
+    let color = if days < AGE_RECENT_DAYS {
+        Color::Green
+    } else if days < AGE_STALE_DAYS {
+        Color::Yellow
+    } else {
+        Color::DarkGray
+    };
+    Some(color)
+}
+
+fn truncate_path(path: &Path, width: u16, style: TruncateStyle) -> String {
     let path = path.to_string_lossy();
     let preserve_len: usize = PATH_PRESERVE_WIDTH;
     let width = (width as usize).max(2 * preserve_len + 3);
-    let len = path.len();
+    let chars: Vec<char> = path.chars().collect();
+    let len = chars.len();
     if len <= width {
         return path.to_string();
     }
-    format!(
-        "{}...{}",
-        &path[0..preserve_len],
-        &path[(len - width + preserve_len + 3)..]
-    )
+    let (head, tail) = match style {
+        TruncateStyle::Head => (0, width - 3),
+        TruncateStyle::Middle => (preserve_len, width - preserve_len - 3),
+        TruncateStyle::Tail => (width - 3, 0),
+    };
+    let head: String = chars[..head].iter().collect();
+    let tail: String = chars[len - tail..].iter().collect();
+    format!("{head}...{tail}")
 }
 
-fn spawn_delete_path(pool: ThreadPool, path: PathBuf, sender: Sender<Message>) {
-    pool.execute(move || delete_path(path, sender));
+fn spawn_delete_path(
+    pool: ThreadPool,
+    paths: Vec<PathBuf>,
+    sender: Sender<Message>,
+    config: Arc<Config>,
+) {
+    pool.execute(move || delete_paths(paths, sender, &config));
 }
 
-fn delete_path(path: PathBuf, sender: Sender<Message>) {
-    match remove_dir_all(&path) {
-        Ok(_) => sender.send(Message::SetPathDeleted(path)).unwrap(),
-        Err(err) => {
-            let msg = Message::PutError(format!("Cannot delete '{}', {}", path.display(), err));
-            sender.send(msg).unwrap()
+/// delete all paths of an item, the first path identifies the item
+fn delete_paths(paths: Vec<PathBuf>, sender: Sender<Message>, config: &Config) {
+    for path in &paths {
+        if let Err(err) = remove_path(path, config) {
+            if let Some(summary) = &config.summary {
+                summary.lock().unwrap().add_error();
+            }
+            let msg = format!("Cannot delete '{}', {}", path.display(), err);
+            sender
+                .send(Message::SetPathFailed(paths[0].clone(), msg))
+                .unwrap();
+            return;
         }
     }
+    sender
+        .send(Message::SetPathDeleted(paths[0].clone()))
+        .unwrap()
 }
@@ -1,8 +1,37 @@
-use anyhow::{anyhow, bail, Context, Error, Result};
+use crate::error::{ProjcleanError, Result};
 use std::cmp::Ordering;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    str::FromStr,
+};
+
+/// built-in rules with their short names
+pub const RULES: [(&str, &str); 20] = [
+    ("nodejs", "node_modules"),
+    ("cargo", "target@Cargo.toml"),
+    ("maven", "target@pom.xml"),
+    ("gradle", ".gradle,build@build.gradle,build.gradle.kts"),
+    ("cmake", "build@CMakeLists.txt"),
+    ("composer", "vendor@composer.json"),
+    ("dotnet", "bin,obj@*.csproj,*.fsproj"),
+    ("vs", ".vs,Debug,Release@*.sln"),
+    ("vc++", "Debug,Release@*.vcxproj"),
+    ("swift", ".build,.swiftpm@Package.swift"),
+    ("pod", "Pods@Podfile"),
+    ("pub", ".dart_tool,build@pubspec.yaml"),
+    ("sbt", "target,project/target@build.sbt"),
+    ("jupyter", ".ipynb_checkpoints@*.ipynb"),
+    ("zig", "zig-cache,zig-out@build.zig"),
+    ("rebar", "_build@rebar.config"),
+    ("dune", "_build@dune-project"),
+    ("mix", "_build@mix.exs"),
+    ("stack", ".stack-work@stack.yaml"),
+    ("godot", ".godot@project.godot"),
+];
 
 /// storage space unit
 static UNITS: [char; 4] = ['T', 'G', 'M', 'K'];
@@ -11,38 +40,824 @@ static UNITS: [char; 4] = ['T', 'G', 'M', 'K'];
 pub struct Config {
     pub rules: Vec<Rule>,
     pub exclude: Vec<String>,
+    /// absolute subtrees excluded from search
+    pub exclude_paths: Vec<PathBuf>,
     pub time: Option<(usize, Ordering)>,
+    /// targets modified within this window are skipped
+    pub skip_recent: Option<Duration>,
     pub size: Option<(u64, Ordering)>,
+    /// percentage that an exact size can be off by
+    pub size_tolerance: Option<u64>,
+    pub delete_order: Option<DeleteOrder>,
+    pub free: Option<u64>,
+    pub no_wrap: bool,
+    pub unknown: bool,
+    pub timeout: Option<Duration>,
+    pub atime: bool,
+    pub per_project: bool,
+    pub age_color: bool,
+    pub top: Option<usize>,
+    pub group_deleted: bool,
+    pub sorted_output: bool,
+    pub yes: bool,
+    pub relative: bool,
+    /// subtrees whose targets are skipped unless `include_archived`
+    pub archives: Vec<glob::Pattern>,
+    pub include_archived: bool,
+    pub max_results: Option<usize>,
+    pub fs_info: bool,
+    pub no_hardlink_dedup: bool,
+    /// skip computing the sizes of targets
+    pub no_size: bool,
+    /// compute the sizes in the TUI once the targets are shown
+    pub lazy_size: bool,
+    /// skip targets with fewer files
+    pub min_files: Option<usize>,
+    /// only report the targets without files
+    pub empty_dirs: bool,
+    /// search inside the matched dirs for the targets of other rules
+    pub recurse_into_matches: bool,
+    /// keep the most recently modified targets of each rule
+    pub keep_recent: Option<usize>,
+    /// only report the targets taking at least this percentage of the total size
+    pub size_percent: Option<u64>,
+    pub watch: bool,
+    pub units: SizeUnits,
+    /// file to write the paths that failed to delete
+    pub fail_log: Option<PathBuf>,
+    /// file to save the TUI items to on exit
+    pub save_session: Option<PathBuf>,
+    /// file to restore the TUI items from instead of searching
+    pub load_session: Option<PathBuf>,
+    pub delete_backend: DeleteBackend,
+    pub truncate: TruncateStyle,
+    pub max_items: Option<usize>,
+    /// template for each item in print mode, see `PathItem::format`
+    pub format: Option<String>,
+    pub color: ColorMode,
+    /// removed from the start of printed paths
+    pub strip_prefix: Option<PathBuf>,
+    /// print a line with the search root and date before the results
+    pub header: bool,
+    /// print the size and the time taken by each deletion
+    pub verbose: bool,
+    /// skip deleting dirs that a process has open files in, only supported on Linux
+    pub check_in_use: bool,
+    /// delete the targets that are symlinks, only the links are removed
+    pub delete_symlinks: bool,
+    /// canonicalized dirs that are never deleted along with one of their ancestors
+    pub protected_paths: Vec<PathBuf>,
+    /// canonicalized search roots that every deletion must stay inside, unchecked when empty
+    pub scan_roots: Vec<PathBuf>,
+    /// printed after each result instead of a newline
+    pub separator: Option<String>,
+    /// capture the mouse in the TUI
+    pub mouse: bool,
+    /// counters of the run shared with `main`, used with `--json-summary`
+    pub summary: Option<Arc<Mutex<Summary>>>,
+    /// diagnostics shared by the searches, only tracked with `--stats`
+    pub scan_stats: Option<Arc<ScanStats>>,
+}
+
+/// whether colors and other escape sequences are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// only on a terminal and unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Auto => "auto",
+            ColorMode::Always => "always",
+            ColorMode::Never => "never",
+        }
+    }
+
+    pub fn enabled(&self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Auto => {
+                is_terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = ProjcleanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(ProjcleanError::InvalidValue("color")),
+        }
+    }
+}
+
+/// how targets are deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeleteBackend {
+    /// `std::fs::remove_dir_all`
+    Std,
+    /// the `remove_dir_all` crate, retried after clearing read-only permissions
+    #[default]
+    Robust,
+    /// move into the trash
+    Trash,
+}
+
+impl DeleteBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeleteBackend::Std => "std",
+            DeleteBackend::Robust => "robust",
+            DeleteBackend::Trash => "trash",
+        }
+    }
+}
+
+impl FromStr for DeleteBackend {
+    type Err = ProjcleanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "std" => Ok(DeleteBackend::Std),
+            "robust" => Ok(DeleteBackend::Robust),
+            "trash" => Ok(DeleteBackend::Trash),
+            _ => Err(ProjcleanError::InvalidValue("delete backend")),
+        }
+    }
+}
+
+/// which part of a long path is cut in the TUI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TruncateStyle {
+    /// keep the end, e.g. `...app/node_modules`
+    Head,
+    /// keep both ends, e.g. `projects/web...node_modules`
+    #[default]
+    Middle,
+    /// keep the start, e.g. `projects/web/app...`
+    Tail,
+}
+
+impl TruncateStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TruncateStyle::Head => "head",
+            TruncateStyle::Middle => "middle",
+            TruncateStyle::Tail => "tail",
+        }
+    }
+}
+
+impl FromStr for TruncateStyle {
+    type Err = ProjcleanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "head" => Ok(TruncateStyle::Head),
+            "middle" => Ok(TruncateStyle::Middle),
+            "tail" => Ok(TruncateStyle::Tail),
+            _ => Err(ProjcleanError::InvalidValue("truncate")),
+        }
+    }
+}
+
+/// how sizes are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// 1024-based with `K/M/G/T`
+    #[default]
+    Short,
+    /// 1024-based with `KiB/MiB/GiB/TiB`
+    Iec,
+    /// 1000-based with `kB/MB/GB/TB`
+    Si,
+    /// exact bytes with thousands separators
+    Bytes,
+}
+
+impl SizeUnits {
+    /// the size in these units, e.g. `1.2G`
+    pub fn format(&self, size: u64) -> String {
+        format_size(size, *self)
+    }
+
+    fn base(&self) -> u64 {
+        match self {
+            SizeUnits::Short | SizeUnits::Iec => 1024,
+            SizeUnits::Si | SizeUnits::Bytes => 1000,
+        }
+    }
+
+    fn suffixes(&self) -> [&'static str; 4] {
+        match self {
+            SizeUnits::Short => ["T", "G", "M", "K"],
+            SizeUnits::Iec => ["TiB", "GiB", "MiB", "KiB"],
+            SizeUnits::Si => ["TB", "GB", "MB", "kB"],
+            SizeUnits::Bytes => ["", "", "", ""],
+        }
+    }
+
+    /// max width of a formatted size
+    pub fn width(&self) -> u16 {
+        match self {
+            // up to 999 TB
+            SizeUnits::Bytes => 19,
+            _ => 4 + self.suffixes()[0].len() as u16,
+        }
+    }
+}
+
+impl SizeUnits {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SizeUnits::Short => "short",
+            SizeUnits::Iec => "iec",
+            SizeUnits::Si => "si",
+            SizeUnits::Bytes => "bytes",
+        }
+    }
+}
+
+impl FromStr for SizeUnits {
+    type Err = ProjcleanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "short" => Ok(SizeUnits::Short),
+            "iec" => Ok(SizeUnits::Iec),
+            "si" => Ok(SizeUnits::Si),
+            "bytes" => Ok(SizeUnits::Bytes),
+            _ => Err(ProjcleanError::InvalidValue("units")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteOrder {
+    Largest,
+    Smallest,
+}
+
+impl DeleteOrder {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeleteOrder::Largest => "largest",
+            DeleteOrder::Smallest => "smallest",
+        }
+    }
+}
+
+impl FromStr for DeleteOrder {
+    type Err = ProjcleanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "largest" => Ok(DeleteOrder::Largest),
+            "smallest" => Ok(DeleteOrder::Smallest),
+            _ => Err(ProjcleanError::InvalidValue("delete order")),
+        }
+    }
+}
+
+/// a value of the effective config, see `Config::entries`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigValue {
+    Bool(bool),
+    Int(u64),
+    Str(String),
+    List(Vec<String>),
+}
+
+impl ConfigValue {
+    /// convert a value of the TOML config file, `None` for the types `to_toml` never writes
+    pub fn from_toml(value: toml::Value) -> Option<Self> {
+        match value {
+            toml::Value::Boolean(v) => Some(ConfigValue::Bool(v)),
+            toml::Value::Integer(v) => Some(ConfigValue::Int(v.try_into().ok()?)),
+            toml::Value::String(v) => Some(ConfigValue::Str(v)),
+            toml::Value::Array(values) => values
+                .into_iter()
+                .map(|v| match v {
+                    toml::Value::String(v) => Some(v),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(ConfigValue::List),
+            _ => None,
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        let quote = |v: &str| {
+            let v = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+                .replace('\t', "\\t")
+                .replace('\0', "\\u0000");
+            format!("\"{v}\"")
+        };
+        match self {
+            ConfigValue::Bool(v) => v.to_string(),
+            ConfigValue::Int(v) => v.to_string(),
+            ConfigValue::Str(v) => quote(v),
+            ConfigValue::List(v) => {
+                format!(
+                    "[{}]",
+                    v.iter().map(|v| quote(v)).collect::<Vec<_>>().join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ConfigValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ConfigValue::Bool(v) => serializer.serialize_bool(*v),
+            ConfigValue::Int(v) => serializer.serialize_u64(*v),
+            ConfigValue::Str(v) => serializer.serialize_str(v),
+            ConfigValue::List(v) => v.serialize(serializer),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Config {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let entries = self.entries();
+        let mut state = serializer.serialize_map(Some(entries.len()))?;
+        for (key, value) in &entries {
+            state.serialize_entry(key, value)?;
+        }
+        state.end()
+    }
 }
 
 impl Config {
-    pub fn is_rule_no_detect(&self, id: &str) -> bool {
-        if let Some(rule) = self.rules.iter().find(|rule| rule.id.as_str() == id) {
-            rule.no_detect()
-        } else {
-            false
+    /// the effective options, unset ones are skipped
+    pub fn entries(&self) -> Vec<(&'static str, ConfigValue)> {
+        let order = |order: Ordering| match order {
+            Ordering::Greater => "+",
+            Ordering::Less => "-",
+            Ordering::Equal => "",
+        };
+        let list = |v: &[String]| ConfigValue::List(v.to_vec());
+        let mut entries = vec![(
+            "rules",
+            ConfigValue::List(self.rules.iter().map(|v| v.id.clone()).collect()),
+        )];
+        entries.push(("exclude", list(&self.exclude)));
+        let exclude_paths: Vec<String> = self
+            .exclude_paths
+            .iter()
+            .map(|v| v.display().to_string())
+            .collect();
+        entries.push(("exclude_paths", list(&exclude_paths)));
+        let archives: Vec<String> = self.archives.iter().map(|v| v.to_string()).collect();
+        entries.push(("archives", list(&archives)));
+        if let Some((time, ordering)) = self.time {
+            entries.push((
+                "time",
+                ConfigValue::Str(format!("{}{time}", order(ordering))),
+            ));
+        }
+        if let Some((size, ordering)) = self.size {
+            entries.push((
+                "size",
+                ConfigValue::Str(format!("{}{size}", order(ordering))),
+            ));
+        }
+        let optional_ints = [
+            ("timeout", self.timeout.map(|v| v.as_secs())),
+            ("skip_recent", self.skip_recent.map(|v| v.as_secs() / 60)),
+            ("top", self.top.map(|v| v as u64)),
+            ("max_results", self.max_results.map(|v| v as u64)),
+            ("min_files", self.min_files.map(|v| v as u64)),
+            ("size_tolerance", self.size_tolerance),
+            ("keep_recent", self.keep_recent.map(|v| v as u64)),
+            ("size_percent", self.size_percent),
+            ("max_items", self.max_items.map(|v| v as u64)),
+            ("free", self.free),
+        ];
+        for (key, value) in optional_ints {
+            if let Some(value) = value {
+                entries.push((key, ConfigValue::Int(value)));
+            }
+        }
+        if let Some(delete_order) = self.delete_order {
+            entries.push(("delete_order", ConfigValue::Str(delete_order.name().into())));
+        }
+        entries.push(("units", ConfigValue::Str(self.units.name().into())));
+        entries.push(("color", ConfigValue::Str(self.color.name().into())));
+        entries.push((
+            "delete_backend",
+            ConfigValue::Str(self.delete_backend.name().into()),
+        ));
+        entries.push(("truncate", ConfigValue::Str(self.truncate.name().into())));
+        if let Some(format) = &self.format {
+            entries.push(("format", ConfigValue::Str(format.clone())));
+        }
+        if let Some(strip_prefix) = &self.strip_prefix {
+            entries.push((
+                "strip_prefix",
+                ConfigValue::Str(strip_prefix.display().to_string()),
+            ));
+        }
+        if let Some(separator) = &self.separator {
+            entries.push(("separator", ConfigValue::Str(separator.clone())));
+        }
+        if let Some(fail_log) = &self.fail_log {
+            entries.push(("fail_log", ConfigValue::Str(fail_log.display().to_string())));
+        }
+        if let Some(file) = &self.save_session {
+            entries.push(("save_session", ConfigValue::Str(file.display().to_string())));
+        }
+        if let Some(file) = &self.load_session {
+            entries.push(("load_session", ConfigValue::Str(file.display().to_string())));
+        }
+        let flags = [
+            ("no_wrap", self.no_wrap),
+            ("unknown", self.unknown),
+            ("atime", self.atime),
+            ("per_project", self.per_project),
+            ("age_color", self.age_color),
+            ("group_deleted", self.group_deleted),
+            ("sorted_output", self.sorted_output),
+            ("yes", self.yes),
+            ("relative", self.relative),
+            ("include_archived", self.include_archived),
+            ("fs_info", self.fs_info),
+            ("no_hardlink_dedup", self.no_hardlink_dedup),
+            ("no_size", self.no_size),
+            ("lazy_size", self.lazy_size),
+            ("recurse_into_matches", self.recurse_into_matches),
+            ("empty_dirs", self.empty_dirs),
+            ("header", self.header),
+            ("verbose", self.verbose),
+            ("check_in_use", self.check_in_use),
+            ("delete_symlinks", self.delete_symlinks),
+            ("watch", self.watch),
+            ("mouse", self.mouse),
+        ];
+        for (key, value) in flags {
+            entries.push((key, ConfigValue::Bool(value)));
+        }
+        entries
+    }
+
+    /// the effective options as TOML
+    pub fn to_toml(&self) -> String {
+        self.entries()
+            .iter()
+            .map(|(key, value)| format!("{key} = {}\n", value.to_toml()))
+            .collect()
+    }
+
+    /// set an option from an entry of `entries`, used to load a dumped config
+    pub fn set_entry(&mut self, key: &str, value: ConfigValue) -> Result<()> {
+        let invalid = || ProjcleanError::InvalidEntry(key.to_string());
+        if let Some(flag) = self.flag_mut(key) {
+            let ConfigValue::Bool(value) = value else {
+                return Err(invalid());
+            };
+            *flag = value;
+            return Ok(());
+        }
+        let value = match value {
+            ConfigValue::List(values) => {
+                match key {
+                    "rules" => {
+                        for rule in &values {
+                            self.add_rule(rule)?;
+                        }
+                    }
+                    "exclude" => self.exclude = values,
+                    "exclude_paths" => {
+                        self.exclude_paths = values.into_iter().map(PathBuf::from).collect()
+                    }
+                    "archives" => {
+                        for archive in &values {
+                            self.add_archive(archive)?;
+                        }
+                    }
+                    _ => return Err(unknown_or_invalid(key)),
+                }
+                return Ok(());
+            }
+            ConfigValue::Int(value) => value.to_string(),
+            ConfigValue::Str(value) => value,
+            ConfigValue::Bool(_) => return Err(unknown_or_invalid(key)),
+        };
+        match key {
+            "time" => self.set_time(&value)?,
+            "size" => self.set_size(&value)?,
+            "timeout" => self.set_timeout(&value)?,
+            "skip_recent" => self.set_skip_recent(&value)?,
+            "top" => self.set_top(&value)?,
+            "max_results" => self.set_max_results(&value)?,
+            "min_files" => self.set_min_files(&value)?,
+            "size_tolerance" => self.set_size_tolerance(&value)?,
+            "keep_recent" => self.set_keep_recent(&value)?,
+            "size_percent" => self.set_size_percent(&value)?,
+            "max_items" => self.set_max_items(&value)?,
+            "free" => self.set_free(&value)?,
+            "delete_order" => self.set_delete_order(&value)?,
+            "units" => self.set_units(&value)?,
+            "color" => self.set_color(&value)?,
+            "delete_backend" => self.set_delete_backend(&value)?,
+            "truncate" => self.set_truncate(&value)?,
+            "format" => self.format = Some(value),
+            "strip_prefix" => self.strip_prefix = Some(PathBuf::from(value)),
+            // dumped as is, so the escapes of `set_separator` are not applied
+            "separator" => self.separator = Some(value),
+            "fail_log" => self.fail_log = Some(PathBuf::from(value)),
+            "save_session" => self.save_session = Some(PathBuf::from(value)),
+            "load_session" => self.load_session = Some(PathBuf::from(value)),
+            "rules" | "exclude" | "exclude_paths" | "archives" => return Err(invalid()),
+            _ => return Err(ProjcleanError::UnknownKey(key.to_string())),
         }
+        Ok(())
+    }
+
+    fn flag_mut(&mut self, key: &str) -> Option<&mut bool> {
+        let flag = match key {
+            "no_wrap" => &mut self.no_wrap,
+            "unknown" => &mut self.unknown,
+            "atime" => &mut self.atime,
+            "per_project" => &mut self.per_project,
+            "age_color" => &mut self.age_color,
+            "group_deleted" => &mut self.group_deleted,
+            "sorted_output" => &mut self.sorted_output,
+            "yes" => &mut self.yes,
+            "relative" => &mut self.relative,
+            "include_archived" => &mut self.include_archived,
+            "fs_info" => &mut self.fs_info,
+            "no_hardlink_dedup" => &mut self.no_hardlink_dedup,
+            "no_size" => &mut self.no_size,
+            "lazy_size" => &mut self.lazy_size,
+            "recurse_into_matches" => &mut self.recurse_into_matches,
+            "empty_dirs" => &mut self.empty_dirs,
+            "header" => &mut self.header,
+            "verbose" => &mut self.verbose,
+            "check_in_use" => &mut self.check_in_use,
+            "delete_symlinks" => &mut self.delete_symlinks,
+            "watch" => &mut self.watch,
+            "mouse" => &mut self.mouse,
+            _ => return None,
+        };
+        Some(flag)
     }
 
     pub fn add_rule(&mut self, value: &str) -> Result<()> {
-        let rule: Rule = value.parse()?;
-        self.rules.push(rule);
+        let mut rule: Rule = value.parse()?;
+        rule.name = RULES
+            .iter()
+            .find(|(_, v)| *v == rule.id)
+            .map(|(name, _)| name.to_string());
+        self.push_rule(rule);
+        Ok(())
+    }
+
+    pub fn add_rule_definition(&mut self, definition: &RuleDefinition) -> Result<()> {
+        let mut rule: Rule = definition.rule.parse()?;
+        rule.name = Some(definition.name.clone()).filter(|v| !v.is_empty());
+        if let Some(time) = &definition.time {
+            let (order, time) = extract_order(time);
+            let time: usize = time.parse().map_err(|_| ProjcleanError::InvalidTime)?;
+            rule.time = Some((time, order));
+        }
+        self.push_rule(rule);
         Ok(())
     }
 
+    /// add the rule unless one with the same id was added before
+    fn push_rule(&mut self, rule: Rule) {
+        if self.rules.iter().all(|v| v.get_id() != rule.get_id()) {
+            self.rules.push(rule);
+        }
+    }
+
+    /// the short name of the rule, the id for custom rules
+    pub fn rule_name(&self, id: &str) -> String {
+        self.rules
+            .iter()
+            .find(|rule| rule.id.as_str() == id)
+            .and_then(|rule| rule.name.clone())
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    pub fn rule_time(&self, id: &str) -> Option<(usize, Ordering)> {
+        self.rules
+            .iter()
+            .find(|rule| rule.id.as_str() == id)
+            .and_then(|rule| rule.time)
+    }
+
     pub fn set_time(&mut self, time: &str) -> Result<()> {
         let (order, time) = extract_order(time);
-        let time: usize = time.parse().map_err(|_| anyhow!("Invalid time value"))?;
+        let time: usize = time.parse().map_err(|_| ProjcleanError::InvalidTime)?;
         self.time = Some((time, order));
         Ok(())
     }
 
+    pub fn set_skip_recent(&mut self, minutes: &str) -> Result<()> {
+        let minutes: u64 = minutes
+            .parse()
+            .map_err(|_| ProjcleanError::InvalidValue("skip recent"))?;
+        self.skip_recent = Some(Duration::from_secs(minutes * 60));
+        Ok(())
+    }
+
+    /// whether the target was modified within the `skip_recent` window
+    pub fn is_recent(&self, time: Duration) -> bool {
+        self.skip_recent.map(|v| time < v).unwrap_or_default()
+    }
+
     pub fn set_size(&mut self, size: &str) -> Result<()> {
         let (order, size) = extract_order(size);
-        let size: u64 = parse_size(size).ok_or_else(|| anyhow!("Invalid size value"))?;
+        let size: u64 = parse_size(size).ok_or(ProjcleanError::InvalidSize)?;
         self.size = Some((size, order));
         Ok(())
     }
+
+    pub fn set_timeout(&mut self, timeout: &str) -> Result<()> {
+        let timeout: u64 = timeout
+            .parse()
+            .map_err(|_| ProjcleanError::InvalidValue("timeout"))?;
+        self.timeout = Some(Duration::from_secs(timeout));
+        Ok(())
+    }
+
+    pub fn set_top(&mut self, top: &str) -> Result<()> {
+        let top: usize = top
+            .parse()
+            .map_err(|_| ProjcleanError::InvalidValue("top"))?;
+        self.top = Some(top);
+        Ok(())
+    }
+
+    pub fn set_max_results(&mut self, max_results: &str) -> Result<()> {
+        let max_results: usize = max_results
+            .parse()
+            .map_err(|_| ProjcleanError::InvalidValue("max results"))?;
+        self.max_results = Some(max_results);
+        Ok(())
+    }
+
+    pub fn set_min_files(&mut self, min_files: &str) -> Result<()> {
+        let min_files: usize = min_files
+            .parse()
+            .map_err(|_| ProjcleanError::InvalidValue("min files"))?;
+        self.min_files = Some(min_files);
+        Ok(())
+    }
+
+    /// the separator with `\n`, `\t`, `\0` and `\\` escapes replaced
+    pub fn set_separator(&mut self, separator: &str) -> Result<()> {
+        let mut output = String::new();
+        let mut chars = separator.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                output.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => output.push('\n'),
+                Some('t') => output.push('\t'),
+                Some('0') => output.push('\0'),
+                Some('\\') => output.push('\\'),
+                _ => return Err(ProjcleanError::InvalidValue("separator")),
+            }
+        }
+        self.separator = Some(output);
+        Ok(())
+    }
+
+    pub fn set_size_tolerance(&mut self, tolerance: &str) -> Result<()> {
+        let tolerance: u64 = tolerance
+            .trim_end_matches('%')
+            .parse()
+            .ok()
+            .filter(|v| *v <= 100)
+            .ok_or(ProjcleanError::InvalidValue("size tolerance"))?;
+        self.size_tolerance = Some(tolerance);
+        Ok(())
+    }
+
+    pub fn set_size_percent(&mut self, percent: &str) -> Result<()> {
+        let percent: u64 = percent
+            .trim_end_matches('%')
+            .parse()
+            .ok()
+            .filter(|v| *v <= 100)
+            .ok_or(ProjcleanError::InvalidValue("size percent"))?;
+        self.size_percent = Some(percent);
+        Ok(())
+    }
+
+    pub fn set_keep_recent(&mut self, keep_recent: &str) -> Result<()> {
+        let keep_recent: usize = keep_recent
+            .parse()
+            .map_err(|_| ProjcleanError::InvalidValue("keep recent"))?;
+        self.keep_recent = Some(keep_recent);
+        Ok(())
+    }
+
+    pub fn set_max_items(&mut self, max_items: &str) -> Result<()> {
+        let max_items: usize = max_items
+            .parse()
+            .map_err(|_| ProjcleanError::InvalidValue("max items"))?;
+        self.max_items = Some(max_items);
+        Ok(())
+    }
+
+    pub fn set_delete_order(&mut self, order: &str) -> Result<()> {
+        self.delete_order = Some(order.parse()?);
+        Ok(())
+    }
+
+    pub fn set_free(&mut self, free: &str) -> Result<()> {
+        let free: u64 = parse_size(free).ok_or(ProjcleanError::InvalidValue("free"))?;
+        self.free = Some(free);
+        Ok(())
+    }
+
+    pub fn set_delete_backend(&mut self, backend: &str) -> Result<()> {
+        self.delete_backend = backend.parse()?;
+        Ok(())
+    }
+
+    pub fn set_truncate(&mut self, truncate: &str) -> Result<()> {
+        self.truncate = truncate.parse()?;
+        Ok(())
+    }
+
+    pub fn set_units(&mut self, units: &str) -> Result<()> {
+        self.units = units.parse()?;
+        Ok(())
+    }
+
+    pub fn set_color(&mut self, color: &str) -> Result<()> {
+        self.color = color.parse()?;
+        Ok(())
+    }
+
+    pub fn add_archive(&mut self, archive: &str) -> Result<()> {
+        let pattern =
+            glob::Pattern::new(archive).map_err(|_| ProjcleanError::InvalidValue("archive"))?;
+        self.archives.push(pattern);
+        Ok(())
+    }
+
+    /// refuse to delete the path, or any of its ancestors, in `remove_path`
+    pub fn protect_path(&mut self, path: &Path) {
+        if let Ok(path) = std::fs::canonicalize(path) {
+            self.protected_paths.push(path);
+        }
+    }
+
+    /// refuse to delete anything outside the search root in `remove_path`
+    pub fn confine_to_root(&mut self, root: &Path) {
+        if let Ok(root) = std::fs::canonicalize(root) {
+            self.scan_roots.push(root);
+        }
+    }
+
+    pub fn is_path_excluded(&self, path: &Path) -> bool {
+        self.exclude_paths.iter().any(|v| path.starts_with(v))
+    }
+
+    /// whether the dir is inside an archive, checked against its absolute and relative path
+    pub fn is_archived(&self, path: &Path, relative_path: &Path) -> bool {
+        self.archives.iter().any(|pattern| {
+            path.ancestors()
+                .chain(relative_path.ancestors())
+                .any(|v| pattern.matches_path(v))
+        })
+    }
+}
+
+/// the error of a key whose value has the wrong type, or that is not an option
+fn unknown_or_invalid(key: &str) -> ProjcleanError {
+    match Config::default().set_entry(key, ConfigValue::Str(String::new())) {
+        Err(ProjcleanError::UnknownKey(_)) => ProjcleanError::UnknownKey(key.to_string()),
+        _ => ProjcleanError::InvalidEntry(key.to_string()),
+    }
 }
 
 fn extract_order(value: &str) -> (Ordering, &str) {
@@ -51,28 +866,48 @@ fn extract_order(value: &str) -> (Ordering, &str) {
     } else if let Some(value) = value.strip_prefix('-') {
         (Ordering::Less, value)
     } else {
-        (Ordering::Equal, value)
+        (Ordering::Equal, value.strip_prefix('=').unwrap_or(value))
     }
 }
 
 fn parse_size(value: &str) -> Option<u64> {
-    for (i, ch) in UNITS.into_iter().rev().enumerate() {
-        if let Some(value) = value.strip_suffix(ch) {
-            let unit = 1024_u64.pow((i + 1) as _);
-            let value: f64 = value.parse().ok()?;
-            let value = value * (unit as f64);
-            return Some(value as u64);
-        }
+    let (value, unit) = UNITS
+        .into_iter()
+        .rev()
+        .enumerate()
+        .find_map(|(i, ch)| {
+            value
+                .strip_suffix(ch)
+                .map(|v| (v, 1024_u64.pow((i + 1) as _)))
+        })
+        .unwrap_or((value, 1));
+    // use float math only for decimals so that whole numbers stay exact
+    if value.contains('.') {
+        let value: f64 = value.parse().ok()?;
+        Some((value * (unit as f64)) as u64)
+    } else {
+        let value: u64 = value.parse().ok()?;
+        value.checked_mul(unit)
     }
-    let value: f64 = value.parse().ok()?;
-    Some(value as u64)
 }
 
 #[derive(Debug, Clone)]
 pub struct Rule {
     id: String,
     targets: HashMap<String, Vec<String>>,
-    detects: Vec<glob::Pattern>,
+    target_patterns: Vec<(glob::Pattern, Vec<String>)>,
+    /// groups of detect patterns with optional required content of the matched file, a group
+    /// is satisfied when all its patterns are present
+    detects: Vec<Vec<(glob::Pattern, Option<String>)>>,
+    max_depth: Option<usize>,
+    /// default last modified filter from the rule definition
+    time: Option<(usize, Ordering)>,
+    /// files that mark the dir containing them as a target, e.g. `CACHEDIR.TAG`
+    markers: Vec<String>,
+    /// purges of a marked dir, which is purged as a whole
+    marker_purges: Vec<String>,
+    /// short name of a built-in or defined rule, e.g. `cargo`
+    name: Option<String>,
 }
 
 impl Rule {
@@ -81,42 +916,150 @@ impl Rule {
     }
 
     pub fn check_target(&self, name: &str) -> Option<&Vec<String>> {
-        self.targets.get(name)
+        self.targets.get(name).or_else(|| {
+            self.target_patterns
+                .iter()
+                .find(|(pattern, _)| pattern.matches(name))
+                .map(|(_, purges)| purges)
+        })
+    }
+
+    /// the purges of the entry when it is a dir containing a marker file
+    pub fn check_marker(&self, dir: &Path, name: &str) -> Option<&Vec<String>> {
+        let path = dir.join(name);
+        self.markers
+            .iter()
+            .any(|marker| path.join(marker).is_file())
+            .then_some(&self.marker_purges)
     }
 
     pub fn no_detect(&self) -> bool {
         self.detects.is_empty()
     }
 
-    pub fn check_project(&self, name: &str) -> bool {
-        if self.no_detect() {
-            false
-        } else {
-            self.detects.iter().any(|v| v.matches(name))
+    pub fn check_depth(&self, depth: usize) -> bool {
+        self.max_depth.map(|v| depth <= v).unwrap_or(true)
+    }
+
+    /// positions of the detect patterns, as (group, index), that the entry satisfies
+    pub fn detect_hits(&self, dir: &Path, name: &str) -> Vec<(usize, usize)> {
+        let mut output = vec![];
+        for (i, group) in self.detects.iter().enumerate() {
+            for (j, (pattern, content)) in group.iter().enumerate() {
+                let hit = match content {
+                    _ if !pattern.matches(name) => false,
+                    None => true,
+                    Some(content) => std::fs::read_to_string(dir.join(name))
+                        .map(|v| v.contains(content.as_str()))
+                        .unwrap_or_default(),
+                };
+                if hit {
+                    output.push((i, j));
+                }
+            }
         }
+        output
+    }
+
+    /// whether every pattern of some detect group was hit
+    pub fn detects_satisfied(&self, hits: &HashSet<(usize, usize)>) -> bool {
+        self.detects
+            .iter()
+            .enumerate()
+            .any(|(i, group)| (0..group.len()).all(|j| hits.contains(&(i, j))))
     }
 }
 
 impl FromStr for Rule {
-    type Err = Error;
+    type Err = ProjcleanError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s;
+        let (s, max_depth) = match s.rsplit_once(':') {
+            Some((v1, v2)) if v2.trim().chars().all(|c| c.is_ascii_digit()) => (
+                v1,
+                Some(v2.trim().parse::<usize>().map_err(|_| {
+                    ProjcleanError::InvalidRule(format!(
+                        "Invalid rule '{}', bad depth '{}'",
+                        id, v2
+                    ))
+                })?),
+            ),
+            Some((_, v2)) if !s.contains('@') => {
+                return Err(ProjcleanError::InvalidRule(format!(
+                    "Invalid rule '{}', bad depth '{}'",
+                    id, v2
+                )))
+            }
+            _ => (s, None),
+        };
         let (target_paths, detects) = match s.split_once('@') {
             Some((v1, v2)) => (v1.trim().split(',').collect::<Vec<&str>>(), v2.trim()),
             None => (s.split(',').collect(), ""),
         };
-        let err_msg = || format!("Invalid rule '{}'", s);
+        let err_msg = || format!("Invalid rule '{}'", id);
         if target_paths.is_empty() {
-            bail!("{}", err_msg())
+            return Err(ProjcleanError::InvalidRule(err_msg()));
+        }
+        if target_paths == [""] {
+            // `@marker`, any dir containing one of the marker files is a target
+            let markers: Vec<String> = detects.split(',').map(|v| v.trim().to_string()).collect();
+            if markers
+                .iter()
+                .any(|v| v.is_empty() || v.contains(['*', '?', '[', '+', ':', '/', '\\']))
+            {
+                return Err(ProjcleanError::InvalidRule(format!(
+                    "{}, markers must be plain file names",
+                    err_msg()
+                )));
+            }
+            return Ok(Rule {
+                id: id.to_string(),
+                targets: HashMap::new(),
+                target_patterns: vec![],
+                detects: vec![],
+                max_depth,
+                time: None,
+                markers,
+                marker_purges: vec![String::new()],
+                name: None,
+            });
         }
         let detects = if detects.is_empty() {
             vec![]
         } else {
             detects
                 .split(',')
-                .map(|v| glob::Pattern::new(v).with_context(err_msg))
-                .collect::<Result<_>>()?
+                .map(|group| {
+                    group
+                        .split('+')
+                        .map(|v| {
+                            let (v, content) = match v.split_once(':') {
+                                Some((v, content)) if !content.is_empty() => {
+                                    (v, Some(content.to_string()))
+                                }
+                                _ => (v, None),
+                            };
+                            glob::Pattern::new(v)
+                                .map(|pattern| (pattern, content))
+                                .map_err(|_| ProjcleanError::InvalidRule(err_msg()))
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+                .collect::<Result<Vec<_>>>()?
         };
+        if let Some(target) = target_paths.iter().find(|target| {
+            detects
+                .iter()
+                .flatten()
+                .any(|(v, _): &(glob::Pattern, _)| v.as_str() == **target)
+        }) {
+            return Err(ProjcleanError::InvalidRule(format!(
+                "{}, target '{}' cannot be its own detect, remove the '@' part to match it anywhere",
+                err_msg(),
+                target
+            )));
+        }
         let mut targets: HashMap<String, Vec<String>> = HashMap::new();
         for target in target_paths {
             match target.split_once('/') {
@@ -134,20 +1077,235 @@ impl FromStr for Rule {
                 }
             }
         }
+        let mut target_patterns = vec![];
+        for name in targets.keys().cloned().collect::<Vec<String>>() {
+            if name.contains(['*', '?', '[']) {
+                let pattern = glob::Pattern::new(&name)
+                    .map_err(|_| ProjcleanError::InvalidRule(err_msg()))?;
+                let purges = targets.remove(&name).unwrap_or_default();
+                target_patterns.push((pattern, purges));
+            }
+        }
         Ok(Rule {
-            id: s.to_string(),
+            id: id.to_string(),
             detects,
             targets,
+            target_patterns,
+            max_depth,
+            time: None,
+            markers: vec![],
+            marker_purges: vec![],
+            name: None,
         })
     }
 }
 
+/// a named rule, from the built-in catalog or a rules file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleDefinition {
+    pub name: String,
+    pub description: Option<String>,
+    pub rule: String,
+    pub time: Option<String>,
+}
+
+impl RuleDefinition {
+    pub fn new(name: &str, rule: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            rule: rule.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// read and parse a rules file, see `parse_file`
+    pub fn load_file(path: &Path) -> Result<Vec<Self>> {
+        Self::parse_file(&std::fs::read_to_string(path)?)
+    }
+
+    /// parse a TOML rules file, each `[name]` section defines a rule with keys `description`,
+    /// `targets`, `detects` and `time`
+    pub fn parse_file(content: &str) -> Result<Vec<Self>> {
+        let table: toml::Table = toml::from_str(content)
+            .map_err(|err| ProjcleanError::InvalidRule(err.message().to_string()))?;
+        let list = |value| match ConfigValue::from_toml(value) {
+            Some(ConfigValue::List(values)) => Some(values),
+            _ => None,
+        };
+        table
+            .into_iter()
+            .map(|(name, section)| {
+                let toml::Value::Table(section) = section else {
+                    return Err(ProjcleanError::InvalidRule(format!(
+                        "'{name}' is not a rule section"
+                    )));
+                };
+                let mut definition = Self {
+                    name: name.clone(),
+                    ..Default::default()
+                };
+                let (mut targets, mut detects) = (vec![], vec![]);
+                for (key, value) in section {
+                    let err = || {
+                        ProjcleanError::InvalidRule(format!(
+                            "Invalid value of '{key}' in rule '{name}'"
+                        ))
+                    };
+                    match key.as_str() {
+                        "description" => {
+                            definition.description = Some(value.as_str().ok_or_else(err)?.into())
+                        }
+                        "targets" => targets = list(value).ok_or_else(err)?,
+                        "detects" => detects = list(value).ok_or_else(err)?,
+                        "time" => {
+                            definition.time = Some(match value {
+                                toml::Value::String(v) => v,
+                                toml::Value::Integer(v) => v.to_string(),
+                                _ => return Err(err()),
+                            })
+                        }
+                        _ => {
+                            return Err(ProjcleanError::InvalidRule(format!(
+                                "Unknown key '{key}' in rule '{name}'"
+                            )))
+                        }
+                    }
+                }
+                if targets.is_empty() {
+                    return Err(ProjcleanError::InvalidRule(format!(
+                        "Rule '{}' has no targets",
+                        definition.name
+                    )));
+                }
+                definition.rule = targets.join(",");
+                if !detects.is_empty() {
+                    definition.rule = format!("{}@{}", definition.rule, detects.join(","));
+                }
+                Ok(definition)
+            })
+            .collect()
+    }
+}
+
+/// counters printed as JSON with `--json-summary`
+#[derive(Debug, Default)]
+pub struct Summary {
+    found: usize,
+    bytes: u64,
+    deleted: usize,
+    freed: u64,
+    /// deletions that failed, warnings of the search are not counted
+    errors: usize,
+    /// sizes of the found targets, to count the freed space on deletion
+    sizes: HashMap<PathBuf, u64>,
+}
+
+impl Summary {
+    /// the counters as a JSON object
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"found": {}, "bytes": {}, "deleted": {}, "freed": {}, "errors": {}}}"#,
+            self.found, self.bytes, self.deleted, self.freed, self.errors
+        )
+    }
+
+    pub fn add_found(&mut self, item: &PathItem) {
+        let size = item.size.unwrap_or_default();
+        self.found += 1;
+        self.bytes += size;
+        self.sizes.insert(item.path.clone(), size);
+    }
+
+    /// replace the found targets with the ones of a re-scan
+    pub fn sync_found(&mut self, items: &[PathItem]) {
+        self.found = 0;
+        self.bytes = 0;
+        for item in items {
+            self.add_found(item);
+        }
+    }
+
+    pub fn add_deleted(&mut self, path: &Path) {
+        self.deleted += 1;
+        self.freed += self.sizes.get(path).copied().unwrap_or_default();
+    }
+
+    pub fn add_error(&mut self) {
+        self.errors += 1;
+    }
+}
+
+/// diagnostics printed to stderr with `--stats`
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    dirs: AtomicUsize,
+    /// files stat'd to compute the sizes
+    files: AtomicUsize,
+    scan_micros: AtomicU64,
+    du_micros: AtomicU64,
+    matches: Mutex<BTreeMap<String, usize>>,
+}
+
+impl ScanStats {
+    pub fn add_dir(&self) {
+        self.dirs.fetch_add(1, AtomicOrdering::Relaxed);
+    }
+
+    /// count a size computation that stat'd the files
+    pub fn add_du(&self, files: usize, time: Duration) {
+        self.files.fetch_add(files, AtomicOrdering::Relaxed);
+        self.du_micros
+            .fetch_add(time.as_micros() as u64, AtomicOrdering::Relaxed);
+    }
+
+    pub fn add_match(&self, rule_id: &str) {
+        *self
+            .matches
+            .lock()
+            .unwrap()
+            .entry(rule_id.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn add_scan_time(&self, time: Duration) {
+        self.scan_micros
+            .fetch_add(time.as_micros() as u64, AtomicOrdering::Relaxed);
+    }
+
+    /// the diagnostics as lines of text
+    pub fn to_text(&self) -> String {
+        let scan_time = Duration::from_micros(self.scan_micros.load(AtomicOrdering::Relaxed));
+        let du_time = Duration::from_micros(self.du_micros.load(AtomicOrdering::Relaxed));
+        let mut output = format!(
+            "dirs visited: {}\nfiles stat'd for sizes: {}\nscan time: {:.3}s\n  walking: {:.3}s\n  computing sizes: {:.3}s\n",
+            self.dirs.load(AtomicOrdering::Relaxed),
+            self.files.load(AtomicOrdering::Relaxed),
+            scan_time.as_secs_f64(),
+            scan_time.saturating_sub(du_time).as_secs_f64(),
+            du_time.as_secs_f64(),
+        );
+        output.push_str("matches per rule:\n");
+        for (rule_id, count) in self.matches.lock().unwrap().iter() {
+            output.push_str(&format!("  {rule_id}: {count}\n"));
+        }
+        output
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Message {
     AddPath(PathItem),
+    AddUnknown(PathBuf),
     SetPathDeleted(PathBuf),
+    /// the deletion of the target failed or was refused, it can be deleted again
+    SetPathFailed(PathBuf, String),
+    /// size computed after the target was found, used with `lazy_size`
+    SetPathSize(PathBuf, u64),
     PutError(String),
     DoneSearch,
+    /// all targets found by a re-scan, used with `watch`
+    SyncPaths(Vec<PathItem>),
 }
 
 #[derive(Debug)]
@@ -155,16 +1313,32 @@ pub struct PathItem {
     pub path: PathBuf,
     pub relative_path: PathBuf,
     pub rule_id: String,
+    /// short name of the rule, the id for custom rules
+    pub rule_name: String,
     pub time: Option<Duration>,
     pub time_text: String,
     pub size: Option<u64>,
     pub size_text: String,
     pub state: PathState,
+    /// other targets of the same project merged into this item
+    pub merged_paths: Vec<PathBuf>,
+    /// whether the target is inside an archive
+    pub archived: bool,
+    /// type of the filesystem holding the target, used with `fs_info`
+    pub fs_type: Option<String>,
+    /// the detect file that satisfied the rule
+    pub detect: Option<String>,
+    /// the target is a symlink, which is only deleted with `--delete-symlinks`
+    pub symlink: bool,
+    /// units the sizes are formatted with
+    pub units: SizeUnits,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum PathState {
     Normal,
+    /// planned in the TUI preview, deleted once confirmed
+    PendingDelete,
     StartDeleting,
     Deleted,
 }
@@ -176,35 +1350,186 @@ impl PathItem {
         rule_id: &str,
         time: Option<Duration>,
         size: Option<u64>,
+        units: SizeUnits,
     ) -> Self {
-        let size_text = size.map(human_readable_folder_size).unwrap_or_default();
+        let size_text = size.map(|v| units.format(v)).unwrap_or_default();
         let time_text = time
-            .map(|v| {
-                let v = v.as_secs_f64() / 86400.0;
-                let v = v.ceil() as u64;
-                format!("{v}d")
-            })
+            .map(|v| human_age(duration_days(v)))
             .unwrap_or_default();
         PathItem {
             path,
             relative_path,
             rule_id: rule_id.to_string(),
+            rule_name: rule_id.to_string(),
             time,
             time_text,
             size,
             size_text,
             state: PathState::Normal,
+            merged_paths: vec![],
+            archived: false,
+            fs_type: None,
+            detect: None,
+            symlink: false,
+            units,
         }
     }
+
+    pub fn all_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.path.clone()];
+        paths.extend(self.merged_paths.iter().cloned());
+        paths
+    }
+
+    /// fill the `{path}`, `{relative_path}`, `{rule}`, `{rule_name}`, `{size}`, `{time}`, `{age}`,
+    /// `{days}` and `{detect}` placeholders of the template
+    pub fn format(&self, template: &str) -> String {
+        let days = self
+            .time
+            .map(|v| duration_days(v).to_string())
+            .unwrap_or_default();
+        template
+            .replace("{path}", &self.path.display().to_string())
+            .replace("{relative_path}", &self.relative_path.display().to_string())
+            .replace("{rule}", &self.rule_id)
+            .replace("{rule_name}", &self.rule_name)
+            .replace("{size}", &self.size_text)
+            .replace("{time}", &self.time_text)
+            .replace("{age}", &self.time_text)
+            .replace("{days}", &days)
+            .replace("{detect}", self.detect.as_deref().unwrap_or_default())
+    }
 }
 
-pub fn human_readable_folder_size(size: u64) -> String {
+impl std::fmt::Display for PathItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.relative_path.display())?;
+        if !self.time_text.is_empty() {
+            write!(f, " {}", self.time_text)?;
+        }
+        if !self.size_text.is_empty() {
+            write!(f, " {}", self.size_text)?;
+        }
+        if self.archived {
+            write!(f, " (archived)")?;
+        }
+        if let Some(fs_type) = &self.fs_type {
+            write!(f, " ({fs_type})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PathItem {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("PathItem", 8)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("relative_path", &self.relative_path)?;
+        state.serialize_field("rule_id", &self.rule_id)?;
+        state.serialize_field("rule_name", &self.rule_name)?;
+        state.serialize_field("size", &self.size)?;
+        state.serialize_field("time_days", &self.time.map(duration_days))?;
+        state.serialize_field("detect", &self.detect)?;
+        state.serialize_field("symlink", &self.symlink)?;
+        state.end()
+    }
+}
+
+/// expand a leading `~` or `~user` and `$VAR`, `${VAR}` or `%VAR%` references,
+/// undefined variables and unknown users are left as is
+pub fn expand_path(value: &str) -> String {
+    expand_path_with(value, |name| std::env::var(name).ok())
+}
+
+fn expand_path_with(value: &str, get_env: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::new();
+    let mut rest = value;
+    if let Some(stripped) = value.strip_prefix('~') {
+        let end = stripped.find(['/', '\\']).unwrap_or(stripped.len());
+        let user = &stripped[..end];
+        let home = if user.is_empty() {
+            get_env("HOME").or_else(|| get_env("USERPROFILE"))
+        } else {
+            user_home(user)
+        };
+        if let Some(home) = home {
+            output.push_str(&home);
+            rest = &stripped[end..];
+        }
+    }
+    while !rest.is_empty() {
+        let var = if let Some(stripped) = rest.strip_prefix("${") {
+            stripped.find('}').map(|end| (&stripped[..end], end + 3))
+        } else if let Some(stripped) = rest.strip_prefix('$') {
+            let end = stripped
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(stripped.len());
+            (end > 0).then(|| (&stripped[..end], end + 1))
+        } else if let Some(stripped) = rest.strip_prefix('%') {
+            stripped
+                .find('%')
+                .filter(|end| *end > 0)
+                .map(|end| (&stripped[..end], end + 2))
+        } else {
+            None
+        };
+        match var.and_then(|(name, len)| get_env(name).map(|v| (v, len))) {
+            Some((value, len)) => {
+                output.push_str(&value);
+                rest = &rest[len..];
+            }
+            None => {
+                let len = rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+                output.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+        }
+    }
+    output
+}
+
+/// home dir of the user from `/etc/passwd`
+#[cfg(unix)]
+fn user_home(user: &str) -> Option<String> {
+    let content = std::fs::read_to_string("/etc/passwd").ok()?;
+    content.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        (fields.len() > 5 && fields[0] == user).then(|| fields[5].to_string())
+    })
+}
+
+#[cfg(not(unix))]
+fn user_home(_user: &str) -> Option<String> {
+    None
+}
+
+/// days of the duration, rounded up
+pub fn duration_days(time: Duration) -> u64 {
+    (time.as_secs_f64() / 86400.0).ceil() as u64
+}
+
+/// age in the largest fitting unit, e.g. `6d`, `2w`, `5mo` or `1y`
+pub fn human_age(days: u64) -> String {
+    match days {
+        0..=6 => format!("{days}d"),
+        7..=29 => format!("{}w", days / 7),
+        30..=364 => format!("{}mo", days / 30),
+        _ => format!("{}y", days / 365),
+    }
+}
+
+fn format_size(size: u64, units: SizeUnits) -> String {
     if size == 0 {
         return size.to_string();
     }
-    for (i, u) in UNITS.iter().enumerate() {
-        let num: u64 = 1024;
-        let marker = num.pow((UNITS.len() - i) as u32);
+    if units == SizeUnits::Bytes {
+        return group_thousands(size);
+    }
+    let suffixes = units.suffixes();
+    for (i, u) in suffixes.iter().enumerate() {
+        let marker = units.base().pow((suffixes.len() - i) as u32);
         if size >= marker {
             if size / marker < 10 {
                 return format!("{:.1}{}", (size as f32 / marker as f32), u);
@@ -216,12 +1541,29 @@ pub fn human_readable_folder_size(size: u64) -> String {
     format!("{size}")
 }
 
+/// digits of the number grouped by commas, e.g. `1,288,490,188`
+fn group_thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut output = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            output.push(',');
+        }
+        output.push(c);
+    }
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_rule() {
+        let rule: Rule = "@CACHEDIR.TAG".parse().unwrap();
+        assert!(rule.no_detect());
+        assert_eq!(rule.check_target("CACHEDIR.TAG"), None);
+        assert!("@*.TAG".parse::<Rule>().is_err());
         let rule: Rule = "target".parse().unwrap();
         assert!(rule.no_detect());
         assert_eq!(
@@ -237,7 +1579,100 @@ mod tests {
         assert_eq!(rule.check_target("Debug"), Some(&vec!["Debug".to_string()]));
         assert_eq!(rule.check_target("Debug-"), None);
         assert_eq!(rule.check_target("-Debug"), None);
-        assert!(rule.check_project("App.sln"));
+        assert!(!rule.detect_hits(Path::new("."), "App.sln").is_empty());
+
+        assert!("target@target".parse::<Rule>().is_err());
+        assert!("*.log@*.log".parse::<Rule>().is_err());
+        assert!("bin,obj@obj".parse::<Rule>().is_err());
+
+        let rule: Rule = "node_modules@package.json:3".parse().unwrap();
+        assert!(rule.check_depth(3));
+        assert!(!rule.check_depth(4));
+        assert!(!rule.detect_hits(Path::new("."), "package.json").is_empty());
+        assert!("node_modules:x".parse::<Rule>().is_err());
+
+        let rule: Rule = "target@Cargo.toml:[package]:2".parse().unwrap();
+        assert!(rule.check_depth(2));
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert!(!rule.detect_hits(dir, "Cargo.toml").is_empty());
+        assert!(rule.detect_hits(&dir.join("src"), "Cargo.toml").is_empty());
+
+        let rule: Rule = "*.pyc,.DS_Store".parse().unwrap();
+        assert_eq!(rule.check_target("a.pyc"), Some(&vec!["*.pyc".to_string()]));
+        assert_eq!(
+            rule.check_target(".DS_Store"),
+            Some(&vec![".DS_Store".to_string()])
+        );
+        assert_eq!(rule.check_target("a.py"), None);
+    }
+
+    #[test]
+    fn test_parse_rule_definitions() {
+        let content = r#"
+# rules shared by the team
+[terraform]
+description = "Terraform providers"
+targets = [".terraform"]
+detects = ["*.tf"]
+time = "+30"
+
+[logs]
+targets = ["*.log", "logs"]
+"#;
+        let definitions = RuleDefinition::parse_file(content).unwrap();
+        assert_eq!(
+            definitions,
+            vec![
+                RuleDefinition {
+                    name: "terraform".into(),
+                    description: Some("Terraform providers".into()),
+                    rule: ".terraform@*.tf".into(),
+                    time: Some("+30".into()),
+                },
+                RuleDefinition::new("logs", "*.log,logs"),
+            ]
+        );
+        assert!(RuleDefinition::parse_file("targets = a").is_err());
+        assert!(RuleDefinition::parse_file("[a]\ndetects = b").is_err());
+        assert!(RuleDefinition::parse_file("targets = [\"a\"]").is_err());
+        assert!(RuleDefinition::parse_file("[a]\ndetects = [\"b\"]").is_err());
+        assert!(RuleDefinition::parse_file("[a]\ntargets = [1]").is_err());
+        assert!(RuleDefinition::parse_file("[a]\ntargets = [\"b\"]\nfoo = 1").is_err());
+        let definitions =
+            RuleDefinition::parse_file("[a]\ntargets = [\n  \"b\",\n  \"c\",\n]").unwrap();
+        assert_eq!(definitions, vec![RuleDefinition::new("a", "b,c")]);
+    }
+
+    #[test]
+    fn test_parse_rule_definitions_comments() {
+        let content = r##"
+  # cached providers
+
+    [terraform]   # infra
+    targets = [".terraform"]  # downloaded by `terraform init`
+
+    detects = ["*.tf"]
+	# keep logs of the last month
+[logs]
+	targets = ["*.log", "#tmp"]
+	time = "+30" # days
+"##;
+        let mut config = Config::default();
+        for definition in RuleDefinition::parse_file(content).unwrap() {
+            config.add_rule_definition(&definition).unwrap();
+        }
+        let rules: Vec<(&str, Option<(usize, Ordering)>)> = config
+            .rules
+            .iter()
+            .map(|v| (v.id.as_str(), v.time))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                (".terraform@*.tf", None),
+                ("*.log,#tmp", Some((30, Ordering::Greater))),
+            ]
+        );
     }
 
     #[test]
@@ -245,6 +1680,122 @@ mod tests {
         assert_eq!(extract_order("+10"), (Ordering::Greater, "10"));
         assert_eq!(extract_order("10"), (Ordering::Equal, "10"));
         assert_eq!(extract_order("-10"), (Ordering::Less, "10"));
+        assert_eq!(extract_order("=10"), (Ordering::Equal, "10"));
+    }
+
+    #[test]
+    fn test_format_size() {
+        let size = 1536 * 1024;
+        assert_eq!(format_size(size, SizeUnits::Short), "1.5M");
+        assert_eq!(format_size(size, SizeUnits::Iec), "1.5MiB");
+        assert_eq!(format_size(size, SizeUnits::Si), "1.6MB");
+        assert_eq!(format_size(500, SizeUnits::Si), "500");
+        assert_eq!(format_size(0, SizeUnits::Iec), "0");
+        assert_eq!(format_size(999, SizeUnits::Bytes), "999");
+        assert_eq!(format_size(1000, SizeUnits::Bytes), "1,000");
+        assert_eq!(format_size(1288490188, SizeUnits::Bytes), "1,288,490,188");
+        assert!("kb".parse::<SizeUnits>().is_err());
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let get_env = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "CODE" => Some("code".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_path_with("~/code", get_env), "/home/me/code");
+        assert_eq!(expand_path_with("~", get_env), "/home/me");
+        assert_eq!(expand_path_with("a~/b", get_env), "a~/b");
+        assert_eq!(expand_path_with("~/$CODE/x", get_env), "/home/me/code/x");
+        assert_eq!(expand_path_with("${CODE}x/%CODE%", get_env), "codex/code");
+        assert_eq!(
+            expand_path_with("$UNDEFINED/%NOPE%", get_env),
+            "$UNDEFINED/%NOPE%"
+        );
+        assert_eq!(expand_path_with("100%", get_env), "100%");
+        assert_eq!(expand_path_with("~nosuchuser/a", get_env), "~nosuchuser/a");
+    }
+
+    #[test]
+    fn test_config_to_toml() {
+        let mut config = Config::default();
+        config.add_rule("target@Cargo.toml").unwrap();
+        config.set_time("+30").unwrap();
+        config.set_top("3").unwrap();
+        config.exclude = vec!["a\"b".into()];
+        let toml = config.to_toml();
+        assert!(toml.starts_with("rules = [\"target@Cargo.toml\"]\nexclude = [\"a\\\"b\"]\n"));
+        assert!(toml.contains("\ntime = \"+30\"\n"));
+        assert!(toml.contains("\ntop = 3\n"));
+        assert!(toml.contains("\nunits = \"short\"\n"));
+        assert!(toml.contains("\nwatch = false\n"));
+    }
+
+    #[test]
+    fn test_config_set_entry() {
+        let mut config = Config::default();
+        config.add_rule("target@Cargo.toml").unwrap();
+        config.set_time("+30").unwrap();
+        config.set_units("bytes").unwrap();
+        config.exclude = vec!["a\"b".into()];
+        config.set_separator("\\t").unwrap();
+        config.watch = true;
+        let toml = config.to_toml();
+        let mut loaded = Config::default();
+        for (key, value) in toml::from_str::<toml::Table>(&toml).unwrap() {
+            loaded
+                .set_entry(&key, ConfigValue::from_toml(value).unwrap())
+                .unwrap();
+        }
+        assert_eq!(loaded.to_toml(), toml);
+        assert!(matches!(
+            loaded.set_entry("foo", ConfigValue::Bool(true)),
+            Err(ProjcleanError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            loaded.set_entry("watch", ConfigValue::Int(1)),
+            Err(ProjcleanError::InvalidEntry(_))
+        ));
+        assert!(matches!(
+            loaded.set_entry("rules", ConfigValue::Str("target".into())),
+            Err(ProjcleanError::InvalidEntry(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_separator() {
+        let mut config = Config::default();
+        config.set_separator(", ").unwrap();
+        assert_eq!(config.separator.as_deref(), Some(", "));
+        config.set_separator("\\t\\0\\n\\\\").unwrap();
+        assert_eq!(config.separator.as_deref(), Some("\t\0\n\\"));
+        assert!(config.set_separator("\\x").is_err());
+    }
+
+    #[test]
+    fn test_add_duplicate_rule() {
+        let mut config = Config::default();
+        config.add_rule("target@Cargo.toml").unwrap();
+        config.add_rule("node_modules").unwrap();
+        config.add_rule("target@Cargo.toml").unwrap();
+        let ids: Vec<&str> = config.rules.iter().map(|v| v.get_id()).collect();
+        assert_eq!(ids, vec!["target@Cargo.toml", "node_modules"]);
+    }
+
+    #[test]
+    fn test_rule_name() {
+        let mut config = Config::default();
+        config
+            .add_rule_definition(&RuleDefinition::new("cargo", "target@Cargo.toml"))
+            .unwrap();
+        config.add_rule("node_modules").unwrap();
+        config.add_rule("bin,obj@*.csproj,*.fsproj").unwrap();
+        config.add_rule("build@Makefile").unwrap();
+        assert_eq!(config.rule_name("target@Cargo.toml"), "cargo");
+        assert_eq!(config.rule_name("node_modules"), "nodejs");
+        assert_eq!(config.rule_name("bin,obj@*.csproj,*.fsproj"), "dotnet");
+        assert_eq!(config.rule_name("build@Makefile"), "build@Makefile");
     }
 
     #[test]
@@ -254,5 +1805,42 @@ mod tests {
         assert_eq!(parse_size("1G"), Some(1024 * 1024 * 1024));
         assert_eq!(parse_size("1T"), Some(1024 * 1024 * 1024 * 1024));
         assert_eq!(parse_size("1.2M"), Some(1258291));
+        assert_eq!(parse_size("1G"), Some(1073741824));
+        assert_eq!(parse_size("3T"), Some(3298534883328));
+        assert_eq!(parse_size("123456789"), Some(123456789));
+        assert_eq!(parse_size("18446744073709551615"), Some(u64::MAX));
+        assert_eq!(parse_size("17179869184G"), None);
+    }
+
+    #[test]
+    fn test_color_mode() {
+        let color: ColorMode = "never".parse().unwrap();
+        assert!(!color.enabled(true));
+        let color: ColorMode = "always".parse().unwrap();
+        assert!(color.enabled(false));
+        assert!(!ColorMode::Auto.enabled(false));
+        assert!("yes".parse::<ColorMode>().is_err());
+    }
+
+    #[test]
+    fn test_human_age() {
+        assert_eq!(human_age(0), "0d");
+        assert_eq!(human_age(6), "6d");
+        assert_eq!(human_age(7), "1w");
+        assert_eq!(human_age(8), "1w");
+        assert_eq!(human_age(29), "4w");
+        assert_eq!(human_age(30), "1mo");
+        assert_eq!(human_age(364), "12mo");
+        assert_eq!(human_age(365), "1y");
+        assert_eq!(human_age(800), "2y");
+        let item = PathItem::new(
+            PathBuf::from("/a/b"),
+            PathBuf::from("b"),
+            "b",
+            Some(Duration::from_secs(8 * 86400)),
+            None,
+            SizeUnits::Short,
+        );
+        assert_eq!(item.format("{age} {days}"), "1w 8");
     }
 }
@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+pub type Result<T, E = ProjcleanError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum ProjcleanError {
+    #[error("{0}")]
+    InvalidRule(String),
+    #[error("Invalid size value")]
+    InvalidSize,
+    #[error("Invalid time value")]
+    InvalidTime,
+    #[error("Invalid {0} value")]
+    InvalidValue(&'static str),
+    #[error("Unknown key '{0}'")]
+    UnknownKey(String),
+    #[error("Invalid value of '{0}'")]
+    InvalidEntry(String),
+    #[error("{0}")]
+    InvalidConfig(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -1,131 +1,1447 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use crossbeam_utils::sync::WaitGroup;
 use jwalk::WalkDirGeneric;
 use remove_dir_all::remove_dir_all;
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufRead, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{self, AtomicBool};
-use std::sync::mpsc::{Receiver, Sender};
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use threadpool::ThreadPool;
 
-use crate::{Config, Message, PathItem};
+use crate::app::SPINNER_DOTS;
+use crate::{
+    duration_days, ColorMode, Config, DeleteBackend, DeleteOrder, Message, PathItem, SizeUnits,
+};
 
+/// directory names that commonly hold dependencies or build artifacts
+const ARTIFACT_NAMES: [&str; 22] = [
+    "node_modules",
+    "target",
+    "build",
+    "dist",
+    "out",
+    "bin",
+    "obj",
+    "vendor",
+    ".gradle",
+    ".terraform",
+    ".serverless",
+    ".next",
+    ".nuxt",
+    ".parcel-cache",
+    ".cache",
+    "__pycache__",
+    ".pytest_cache",
+    ".mypy_cache",
+    ".tox",
+    ".venv",
+    "venv",
+    "coverage",
+];
+
+/// filesystems where deleting does not reclaim disk space of the host
+const VOLATILE_FS_TYPES: [&str; 2] = ["tmpfs", "overlay"];
+
+/// targets on a volatile filesystem above this size get a warning
+const VOLATILE_WARN_SIZE: u64 = 100 * 1024 * 1024;
+
+/// interval to animate the print mode spinner
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// interval between re-scans, used with `watch`
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// interval to check whether a paused search is resumed
+const PAUSE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// upper bounds and labels of the size buckets printed by `histogram`
+const HISTOGRAM_BUCKETS: [(u64, &str); 5] = [
+    (1 << 20, "<1M"),
+    (10 << 20, "1-10M"),
+    (100 << 20, "10-100M"),
+    (1 << 30, "100M-1G"),
+    (u64::MAX, ">1G"),
+];
+
+/// width of the longest histogram bar
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+const HTML_TEMPLATE: &str = include_str!("../assets/report.html");
+
+/// state of a matched dir, passed from `process_read_dir` to the walk loop
+#[derive(Debug, Clone, Default)]
+struct MatchState {
+    rule_id: String,
+    purges: Vec<String>,
+    archived: bool,
+    /// the detect file that satisfied the rule
+    detect: Option<String>,
+}
+
+/// the search stops once `running` is cleared and waits while `paused` is set
 pub fn search(
     entry: PathBuf,
     config: Config,
     tx: Sender<Message>,
     running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
 ) -> Result<()> {
     let config_clone = config.clone();
-    let walk_dir = WalkDirGeneric::<((), Option<(String, Vec<String>)>)>::new(entry.clone())
+    let entry_clone = entry.clone();
+    let walk_dir = WalkDirGeneric::<((), Option<MatchState>)>::new(entry.clone())
         .skip_hidden(false)
-        .process_read_dir(move |_depth, _path, _state, children| {
-            let mut checker = Checker::new(&config_clone);
-            for dir_entry in children.iter().flatten() {
-                if let Some(name) = dir_entry.file_name.to_str() {
-                    if config_clone.exclude.contains(&name.to_string()) {
-                        continue;
-                    }
-                    checker.check(name);
+        .process_read_dir(move |depth, path, _state, children| {
+            let archived = !config_clone.archives.is_empty()
+                && config_clone.is_archived(path, path.strip_prefix(&entry_clone).unwrap_or(path));
+            if archived && !config_clone.include_archived {
+                children.iter_mut().flatten().for_each(|dir_entry| {
+                    dir_entry.read_children_path = None;
+                });
+                return;
+            }
+            // depth of the children relative to the search root
+            let depth = depth.map(|v| v + 1).unwrap_or_default();
+            let mut checker = Checker::new(&config_clone, depth, path);
+            // names that are not valid UTF-8 are matched lossily so their entries are still found
+            let names: Vec<_> = children
+                .iter()
+                .flatten()
+                .map(|dir_entry| (dir_entry.file_name.to_string_lossy(), dir_entry.path()))
+                .collect();
+            for (name, path) in &names {
+                if config_clone.exclude.iter().any(|v| v == name)
+                    || config_clone.is_path_excluded(path)
+                {
+                    continue;
                 }
+                checker.check(name);
             }
             let matches = checker.to_matches();
             children.iter_mut().for_each(|dir_entry_result| {
                 if let Ok(dir_entry) = dir_entry_result {
-                    if let Some(name) = dir_entry.file_name.to_str() {
-                        if config_clone.exclude.contains(&name.to_string()) {
+                    let name = dir_entry.file_name.to_string_lossy().to_string();
+                    if config_clone.exclude.contains(&name)
+                        || config_clone.is_path_excluded(&dir_entry.path())
+                    {
+                        dir_entry.read_children_path = None;
+                    } else if let Some((rule_id, purges, detect)) = matches.get(&name) {
+                        if !config_clone.recurse_into_matches {
                             dir_entry.read_children_path = None;
-                        } else if let Some((rule_id, purges)) = matches.get(name) {
-                            dir_entry.read_children_path = None;
-                            dir_entry.client_state = Some((rule_id.to_string(), purges.to_vec()));
                         }
+                        dir_entry.client_state = Some(MatchState {
+                            rule_id: rule_id.to_string(),
+                            purges: purges.to_vec(),
+                            archived,
+                            detect: detect.clone(),
+                        });
+                    } else if config_clone.unknown && is_artifact_like(dir_entry, &name) {
+                        dir_entry.read_children_path = None;
                     }
                 }
             });
         });
 
+    let deadline = config.timeout.map(|v| Instant::now() + v);
+    // whether every checked access time equals its modification time, which hints at `noatime`
+    let mut atime_stale = None;
+    // targets grouped by project dir and rule, used with `per_project`
+    let mut groups: Vec<(PathBuf, Vec<PathItem>)> = vec![];
+    let mut found = 0;
+    let mounts = if config.fs_info {
+        read_mounts()
+    } else {
+        vec![]
+    };
+    // large targets on volatile filesystems, used with `fs_info`
+    let mut volatile_items: Vec<(PathBuf, String)> = vec![];
+    // found targets and their rules, used with `recurse_into_matches`
+    let mut matched: Vec<(PathBuf, String)> = vec![];
+    // how many more targets can be sent, used with `max_results`
+    let remaining = |found: usize| config.max_results.map(|v| v.saturating_sub(found));
+    // only tracked with `--stats`
+    let stats = config.scan_stats.as_deref();
+    let scan_start = Instant::now();
     for dir_entry_result in walk_dir {
+        if config.max_results.map(|v| found >= v).unwrap_or_default() {
+            break;
+        }
+        while paused.load(atomic::Ordering::SeqCst) && running.load(atomic::Ordering::SeqCst) {
+            std::thread::sleep(PAUSE_INTERVAL);
+        }
+        // stop early, the targets found so far are still flushed below
         if !running.load(atomic::Ordering::SeqCst) {
-            let _ = tx.send(Message::DoneSearch);
-            return Ok(());
+            break;
+        }
+        if deadline.map(|v| Instant::now() >= v).unwrap_or_default() {
+            let _ = tx.send(Message::PutError(
+                "Search stopped due to timeout, results may be incomplete".into(),
+            ));
+            break;
+        }
+        if dir_entry_result.is_err() && !entry.exists() {
+            let _ = tx.send(Message::PutError(format!(
+                "Search root '{}' was removed during search",
+                entry.display()
+            )));
+            break;
         }
         if let Ok(dir_entry) = &dir_entry_result {
-            if let Some((rule_id, purges)) = dir_entry.client_state.as_ref() {
+            if config.per_project && !groups.is_empty() {
+                // the targets of a project are siblings, so its group is complete once the walk
+                // has left the project dir
+                let path = dir_entry.path();
+                let (done, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut groups)
+                    .into_iter()
+                    .partition(|(dir, _)| !path.starts_with(dir));
+                groups = pending;
+                found += send_groups(&tx, &entry, done, remaining(found));
+                if remaining(found) == Some(0) {
+                    break;
+                }
+            }
+            if let Some(stats) = stats.filter(|_| dir_entry.file_type.is_dir()) {
+                stats.add_dir();
+            }
+            if config.unknown
+                && dir_entry.client_state.is_none()
+                && is_artifact_like(dir_entry, &dir_entry.file_name.to_string_lossy())
+            {
+                let _ = tx.send(Message::AddUnknown(dir_entry.path()));
+            }
+            if let Some(MatchState {
+                rule_id,
+                purges,
+                archived,
+                detect,
+            }) = dir_entry.client_state.as_ref()
+            {
                 let entry_path = dir_entry.path();
                 for purge in purges {
-                    let mut path = entry_path.clone();
-                    for part in purge.split('/').skip(1) {
-                        path.push(part)
-                    }
+                    let path = purge_path(&entry_path, purge);
                     if !path.exists() {
                         continue;
                     }
-                    let time = last_modified(&path).ok();
-                    if let (Some((expect, order)), Some(time)) = (config.time, time) {
-                        if !compare(order, expect, (time.as_secs_f64() / 86400.0).ceil() as _) {
+                    let time = if config.atime {
+                        last_accessed(&path)
+                            .map(|(time, stale)| {
+                                atime_stale = Some(atime_stale.unwrap_or(true) && stale);
+                                time
+                            })
+                            .ok()
+                    } else {
+                        last_modified(&path).ok()
+                    };
+                    let time_filter = config.time.or_else(|| config.rule_time(rule_id));
+                    if let (Some((expect, order)), Some(time)) = (time_filter, time) {
+                        if !compare(order, expect, duration_days(time) as _) {
                             continue;
                         }
                     }
+                    if time.map(|v| config.is_recent(v)).unwrap_or_default() {
+                        continue;
+                    }
 
-                    let size = du(&path).ok();
-                    if let (Some((expect, order)), Some(size)) = (config.size, size) {
-                        if !compare(order, expect, size) {
+                    let skip_size = config.no_size || config.lazy_size;
+                    let usage = if skip_size && config.min_files.is_none() && !config.empty_dirs {
+                        None
+                    } else {
+                        let du_start = Instant::now();
+                        let usage = du(&path, !config.no_hardlink_dedup, &running).ok();
+                        if let Some(stats) = stats {
+                            let files = usage.map(|(_, files)| files).unwrap_or_default();
+                            stats.add_du(files, du_start.elapsed());
+                        }
+                        usage
+                    };
+                    if !running.load(atomic::Ordering::SeqCst) {
+                        // the size computation was cancelled, the walk loop stops next
+                        continue;
+                    }
+                    if let (Some(min_files), Some((_, files))) = (config.min_files, usage) {
+                        if files < min_files {
+                            continue;
+                        }
+                    }
+                    if config.empty_dirs && usage.map(|(_, files)| files != 0).unwrap_or(true) {
+                        continue;
+                    }
+                    let size = usage.filter(|_| !skip_size).map(|(size, _)| size);
+                    if let Some(size) = size {
+                        if !compare_size(&config, size) {
+                            continue;
+                        }
+                    }
+                    let Ok(relative_path) = path.strip_prefix(&entry).map(|v| v.to_path_buf())
+                    else {
+                        continue;
+                    };
+                    let fs_type = mount_fs_type(&mounts, &path);
+                    if let (Some(fs_type), Some(size)) = (&fs_type, size) {
+                        if VOLATILE_FS_TYPES.contains(&fs_type.as_str())
+                            && size >= VOLATILE_WARN_SIZE
+                        {
+                            volatile_items.push((relative_path.clone(), fs_type.clone()));
+                        }
+                    }
+                    if config.recurse_into_matches {
+                        // a target inside a target of the same rule is deleted with it
+                        if matched
+                            .iter()
+                            .any(|(dir, id)| id == rule_id && path.starts_with(dir))
+                        {
                             continue;
                         }
+                        matched.push((path.clone(), rule_id.clone()));
+                    }
+                    if let Some(stats) = stats {
+                        stats.add_match(rule_id);
+                    }
+                    let mut path_item =
+                        PathItem::new(path, relative_path, rule_id, time, size, config.units);
+                    path_item.rule_name = config.rule_name(rule_id);
+                    path_item.archived = *archived;
+                    path_item.detect = detect.clone();
+                    path_item.fs_type = fs_type;
+                    path_item.symlink = std::fs::symlink_metadata(&path_item.path)
+                        .map(|v| v.file_type().is_symlink())
+                        .unwrap_or_default();
+                    if config.per_project {
+                        let project_dir = entry_path.parent().unwrap_or(&entry).to_path_buf();
+                        match groups.iter_mut().find(|(dir, items)| {
+                            dir == &project_dir && &items[0].rule_id == rule_id
+                        }) {
+                            Some((_, items)) => items.push(path_item),
+                            None => groups.push((project_dir, vec![path_item])),
+                        }
+                    } else if config.max_results.map(|v| found < v).unwrap_or(true) {
+                        let _ = tx.send(Message::AddPath(path_item));
+                        found += 1;
                     }
-                    let relative_path = path.strip_prefix(&entry)?.to_path_buf();
-                    let path_item = PathItem::new(path, relative_path, rule_id, time, size);
-                    let _ = tx.send(Message::AddPath(path_item));
                 }
             }
         }
     }
 
+    send_groups(&tx, &entry, groups, remaining(found));
+
+    for (path, fs_type) in volatile_items {
+        let _ = tx.send(Message::PutError(format!(
+            "'{}' is on {fs_type}, deleting it may not free host disk space",
+            path.display()
+        )));
+    }
+
+    if atime_stale == Some(true) {
+        let _ = tx.send(Message::PutError(
+            "Access times equal modification times, the filesystem may be mounted with noatime and atime filtering may be unreliable".into(),
+        ));
+    }
+
+    if let Some(stats) = stats {
+        stats.add_scan_time(scan_start.elapsed());
+    }
+
+    let _ = tx.send(Message::DoneSearch);
+
+    Ok(())
+}
+
+/// search the roots one after another, each target is relative to its own root
+pub fn search_roots(
+    roots: Vec<PathBuf>,
+    config: Config,
+    tx: Sender<Message>,
+    running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+) -> Result<()> {
+    for entry in roots {
+        if !running.load(atomic::Ordering::SeqCst) {
+            break;
+        }
+        let (root_tx, root_rx) = channel();
+        let forward = {
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                for message in root_rx {
+                    if matches!(message, Message::DoneSearch) {
+                        continue;
+                    }
+                    if tx.send(message).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+        let result = search(
+            entry,
+            config.clone(),
+            root_tx,
+            running.clone(),
+            paused.clone(),
+        );
+        let _ = forward.join();
+        if let Err(err) = result {
+            let _ = tx.send(Message::PutError(err.to_string()));
+        }
+    }
     let _ = tx.send(Message::DoneSearch);
+    Ok(())
+}
 
+/// search, then re-scan periodically and send all targets whenever they change
+pub fn watch(
+    entry: PathBuf,
+    config: Config,
+    tx: Sender<Message>,
+    running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+) -> Result<()> {
+    let mut last_paths: Option<Vec<PathBuf>> = None;
+    while running.load(atomic::Ordering::SeqCst) {
+        let (scan_tx, scan_rx) = channel();
+        let (entry, config) = (entry.clone(), config.clone());
+        let (scan_running, scan_paused) = (running.clone(), paused.clone());
+        std::thread::spawn(move || search(entry, config, scan_tx, scan_running, scan_paused));
+        // the first search is forwarded as is, re-scans are sent at once
+        let mut items = vec![];
+        let mut paths = vec![];
+        for message in scan_rx {
+            match message {
+                Message::AddPath(item) => {
+                    paths.push(item.path.clone());
+                    if last_paths.is_some() {
+                        items.push(item);
+                    } else {
+                        let _ = tx.send(Message::AddPath(item));
+                    }
+                }
+                message if last_paths.is_none() => {
+                    let _ = tx.send(message);
+                }
+                _ => {}
+            }
+        }
+        paths.sort();
+        if last_paths.is_some() && last_paths.as_ref() != Some(&paths) {
+            let _ = tx.send(Message::SyncPaths(items));
+        }
+        last_paths = Some(paths);
+        let deadline = Instant::now() + WATCH_INTERVAL;
+        while Instant::now() < deadline && running.load(atomic::Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+    let _ = tx.send(Message::DoneSearch);
     Ok(())
 }
 
-pub fn ls(rx: Receiver<Message>) -> Result<()> {
+/// forward the targets once the search is done, without the `n` most recently modified ones of
+/// each rule
+pub fn keep_recent(rx: Receiver<Message>, n: usize) -> Receiver<Message> {
+    let (tx, output) = channel();
+    std::thread::spawn(move || {
+        let mut items: BTreeMap<String, Vec<PathItem>> = BTreeMap::new();
+        for message in rx {
+            let message = match message {
+                Message::AddPath(item) => {
+                    items.entry(item.rule_id.clone()).or_default().push(item);
+                    continue;
+                }
+                Message::DoneSearch => {
+                    for (_, mut items) in std::mem::take(&mut items) {
+                        // the time is the age, targets without one are never kept
+                        items.sort_by_key(|v| v.time.unwrap_or(Duration::MAX));
+                        for item in items.into_iter().skip(n) {
+                            let _ = tx.send(Message::AddPath(item));
+                        }
+                    }
+                    Message::DoneSearch
+                }
+                message => message,
+            };
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    output
+}
+
+/// forward the targets once the search is done, only those taking at least `percent` of the
+/// total size, targets without a size are dropped
+pub fn size_percent(rx: Receiver<Message>, percent: u64) -> Receiver<Message> {
+    let (tx, output) = channel();
+    std::thread::spawn(move || {
+        let mut items: Vec<PathItem> = vec![];
+        for message in rx {
+            let message = match message {
+                Message::AddPath(item) => {
+                    items.push(item);
+                    continue;
+                }
+                Message::DoneSearch => {
+                    let total: u64 = items.iter().filter_map(|v| v.size).sum();
+                    for item in std::mem::take(&mut items) {
+                        let size = item.size.unwrap_or_default() as u128;
+                        if item.size.is_some() && size * 100 >= total as u128 * percent as u128 {
+                            let _ = tx.send(Message::AddPath(item));
+                        }
+                    }
+                    Message::DoneSearch
+                }
+                message => message,
+            };
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    output
+}
+
+/// path of a purge below the matched dir, the first segment of the purge is the matched dir
+/// itself, which may differ from it when the target is a glob pattern
+fn purge_path(matched_dir: &Path, purge: &str) -> PathBuf {
+    let mut path = matched_dir.to_path_buf();
+    for part in purge.split(['/', '\\']).skip(1).filter(|v| !v.is_empty()) {
+        path.push(part)
+    }
+    path
+}
+
+/// send the merged targets of at most `limit` groups, returns how many were sent
+fn send_groups(
+    tx: &Sender<Message>,
+    entry: &Path,
+    groups: Vec<(PathBuf, Vec<PathItem>)>,
+    limit: Option<usize>,
+) -> usize {
+    let groups = groups.into_iter().take(limit.unwrap_or(usize::MAX));
+    let mut sent = 0;
+    for (project_dir, items) in groups {
+        let _ = tx.send(Message::AddPath(merge_project_items(
+            entry,
+            &project_dir,
+            items,
+        )));
+        sent += 1;
+    }
+    sent
+}
+
+/// merge the targets of a project into a single item
+fn merge_project_items(entry: &Path, project_dir: &Path, mut items: Vec<PathItem>) -> PathItem {
+    if items.len() == 1 {
+        return items.remove(0);
+    }
+    let names: Vec<String> = items
+        .iter()
+        .map(|v| {
+            v.path
+                .strip_prefix(project_dir)
+                .unwrap_or(&v.path)
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    let relative_path = project_dir
+        .strip_prefix(entry)
+        .unwrap_or(project_dir)
+        .join(format!("{{{}}}", names.join(",")));
+    let size = items.iter().filter_map(|v| v.size).reduce(|a, b| a + b);
+    let time = items.iter().filter_map(|v| v.time).min();
+    let rule_id = items[0].rule_id.clone();
+    let rule_name = items[0].rule_name.clone();
+    let archived = items[0].archived;
+    let units = items[0].units;
+    let detect = items[0].detect.clone();
+    let symlink = items.iter().any(|v| v.symlink);
+    let mut paths = items.into_iter().map(|v| v.path);
+    let path = paths.next().unwrap_or_default();
+    let mut item = PathItem::new(path, relative_path, &rule_id, time, size, units);
+    item.merged_paths = paths.collect();
+    item.rule_name = rule_name;
+    item.archived = archived;
+    item.detect = detect;
+    item.symlink = symlink;
+    item
+}
+
+pub fn ls(rx: Receiver<Message>, config: &Config) -> Result<()> {
+    let mut spinner = Spinner::new(config.color);
+    // the header goes before the first result
+    let mut header = config.header;
+    let mut print_item = |item: &PathItem| {
+        if std::mem::take(&mut header) {
+            let root = std::env::current_dir().unwrap_or_default();
+            println!(
+                "# projclean {} {} UTC",
+                root.display(),
+                format_utc(SystemTime::now())
+            );
+        }
+        print_item(item, config);
+    };
+    if let Some(top) = config.top {
+        let mut items = vec![];
+        while let Some(message) = recv_with_spinner(&rx, &mut spinner) {
+            match message {
+                Message::AddPath(path) => items.push(path),
+                Message::PutError(err) => {
+                    spinner.clear();
+                    eprintln!("{err}");
+                }
+                Message::DoneSearch => break,
+                _ => {}
+            }
+        }
+        spinner.clear();
+        items.sort_by_key(|v| Reverse(v.size.unwrap_or_default()));
+        let rest = items.split_off(top.min(items.len()));
+        for item in items {
+            print_item(&item);
+        }
+        if !rest.is_empty() {
+            let rest_size: u64 = rest.iter().map(|v| v.size.unwrap_or_default()).sum();
+            println!(
+                "and {} more totaling {}",
+                rest.len(),
+                config.units.format(rest_size)
+            );
+        }
+        return Ok(());
+    }
+    // with `watch`, the first search is followed by re-scans until stopped
+    let mut searched = false;
+    while let Some(message) = recv_with_spinner(&rx, &mut spinner) {
+        spinner.clear();
+        match message {
+            Message::AddPath(path) => print_item(&path),
+            Message::PutError(err) => eprintln!("{err}"),
+            Message::DoneSearch if !config.watch || searched => break,
+            Message::DoneSearch => searched = true,
+            Message::SyncPaths(items) => {
+                if config.color.enabled(io::stdout().is_terminal()) {
+                    print!("\x1b[2J\x1b[H");
+                } else {
+                    println!();
+                }
+                for item in items {
+                    print_item(&item);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// spinner on stderr shown while nothing is printed yet, disabled when stderr is redirected
+struct Spinner {
+    enabled: bool,
+    index: usize,
+}
+
+impl Spinner {
+    fn new(color: ColorMode) -> Self {
+        let is_terminal = io::stderr().is_terminal();
+        Self {
+            enabled: is_terminal && color.enabled(is_terminal),
+            index: 0,
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.enabled {
+            eprint!("\r{} scanning…", SPINNER_DOTS[self.index]);
+            self.index = (self.index + 1) % SPINNER_DOTS.len();
+        }
+    }
+
+    fn clear(&mut self) {
+        if self.enabled {
+            eprint!("\r\x1b[K");
+            self.enabled = false;
+        }
+    }
+}
+
+/// receive the next message, animating the spinner while waiting
+fn recv_with_spinner(rx: &Receiver<Message>, spinner: &mut Spinner) -> Option<Message> {
+    loop {
+        match rx.recv_timeout(SPINNER_INTERVAL) {
+            Ok(message) => return Some(message),
+            Err(RecvTimeoutError::Timeout) => spinner.tick(),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+fn print_item(item: &PathItem, config: &Config) {
+    let separator = config.separator.as_deref().unwrap_or("\n");
+    if let Some(format) = &config.format {
+        print!("{}{separator}", item.format(format));
+    } else if config.relative {
+        print!("{}{separator}", item.relative_path.display());
+    } else {
+        for path in item.all_paths() {
+            let path = config
+                .strip_prefix
+                .as_ref()
+                .and_then(|v| path.strip_prefix(v).ok())
+                .unwrap_or(&path);
+            print!("{}{separator}", path.display());
+        }
+    }
+}
+
+pub fn histogram(rx: Receiver<Message>, units: SizeUnits) -> Result<()> {
+    let mut buckets = [(0usize, 0u64); HISTOGRAM_BUCKETS.len()];
+    let items = collect_paths(rx);
+    for item in &items {
+        let size = item.size.unwrap_or_default();
+        let index = HISTOGRAM_BUCKETS
+            .iter()
+            .position(|(bound, _)| size < *bound)
+            .unwrap_or(HISTOGRAM_BUCKETS.len() - 1);
+        buckets[index].0 += 1;
+        buckets[index].1 += size;
+    }
+    let max_count = buckets
+        .iter()
+        .map(|(count, _)| *count)
+        .max()
+        .unwrap_or_default();
+    for ((_, label), (count, size)) in HISTOGRAM_BUCKETS.iter().zip(buckets) {
+        let bar_width = (count * HISTOGRAM_BAR_WIDTH).div_ceil(max_count.max(1));
+        println!(
+            "{:>7} {:>5} {:<width$} {}",
+            label,
+            count,
+            "█".repeat(bar_width),
+            units.format(size),
+            width = HISTOGRAM_BAR_WIDTH
+        );
+    }
+    let total: u64 = buckets.iter().map(|(_, size)| size).sum();
+    println!("Total: {} targets, {}", items.len(), units.format(total));
+    Ok(())
+}
+
+/// node of the tree printed by `tree`
+#[derive(Debug, Default)]
+struct TreeNode {
+    children: BTreeMap<String, TreeNode>,
+    size_text: Option<String>,
+}
+
+pub fn tree(rx: Receiver<Message>) -> Result<()> {
+    let mut root = TreeNode::default();
+    for item in collect_paths(rx) {
+        let mut node = &mut root;
+        for part in item.relative_path.iter() {
+            node = node
+                .children
+                .entry(part.to_string_lossy().to_string())
+                .or_default();
+        }
+        node.size_text = Some(item.size_text);
+    }
+    println!(".");
+    print_tree(&root, "");
+    Ok(())
+}
+
+fn print_tree(node: &TreeNode, prefix: &str) {
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let (branch, indent) = if i + 1 == node.children.len() {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        match &child.size_text {
+            Some(size_text) if !size_text.is_empty() => {
+                println!("{prefix}{branch}{name} {size_text}")
+            }
+            _ => println!("{prefix}{branch}{name}"),
+        }
+        print_tree(child, &format!("{prefix}{indent}"));
+    }
+}
+
+pub fn unknown(rx: Receiver<Message>) -> Result<()> {
+    let mut names = vec![];
     for message in rx {
         match message {
-            Message::AddPath(path) => {
-                println!("{}", path.path.display());
+            Message::AddUnknown(path) => {
+                println!("{}", path.display());
+                if let Some(name) = path.file_name() {
+                    let name = name.to_string_lossy().to_string();
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
             }
+            Message::PutError(err) => eprintln!("{err}"),
             Message::DoneSearch => break,
             _ => {}
         }
     }
+    if !names.is_empty() {
+        eprintln!(
+            "\nThese directories look like build artifacts but no rule matched them, consider adding rules like '{}@<detect>'",
+            names.join(",")
+        );
+    }
+    Ok(())
+}
+
+pub fn save_snapshot(rx: Receiver<Message>, file: &Path) -> Result<()> {
+    let items = collect_paths(rx);
+    let mut output = String::new();
+    for item in &items {
+        println!("{}", item.path.display());
+        output.push_str(&format!(
+            "{}\t{}\n",
+            item.size.unwrap_or_default(),
+            item.path.display()
+        ));
+    }
+    std::fs::write(file, output)
+        .with_context(|| format!("Failed to write snapshot to '{}'", file.display()))?;
+    Ok(())
+}
+
+pub fn diff(rx: Receiver<Message>, file: &Path, units: SizeUnits) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read snapshot from '{}'", file.display()))?;
+    let mut old_sizes: HashMap<PathBuf, u64> = HashMap::new();
+    for line in content.lines() {
+        let (size, path) = line
+            .split_once('\t')
+            .and_then(|(size, path)| Some((size.parse::<u64>().ok()?, path)))
+            .ok_or_else(|| anyhow!("Invalid snapshot line '{}'", line))?;
+        old_sizes.insert(PathBuf::from(path), size);
+    }
+    for item in collect_paths(rx) {
+        let size = item.size.unwrap_or_default();
+        match old_sizes.remove(&item.path) {
+            None => println!("+ {} {}", item.path.display(), item.size_text),
+            Some(old_size) if old_size < size => println!(
+                "~ {} +{}",
+                item.path.display(),
+                units.format(size - old_size)
+            ),
+            Some(old_size) if old_size > size => println!(
+                "~ {} -{}",
+                item.path.display(),
+                units.format(old_size - size)
+            ),
+            Some(_) => {}
+        }
+    }
+    let mut removed: Vec<(PathBuf, u64)> = old_sizes.into_iter().collect();
+    removed.sort();
+    for (path, size) in removed {
+        println!("- {} {}", path.display(), units.format(size));
+    }
+    Ok(())
+}
+
+pub fn html(rx: Receiver<Message>, file: &Path, units: SizeUnits) -> Result<()> {
+    let items = collect_paths(rx);
+    let total_size: u64 = items.iter().map(|v| v.size.unwrap_or_default()).sum();
+    let mut rows = String::new();
+    for item in &items {
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td data-value=\"{}\">{}</td><td data-value=\"{}\">{}</td></tr>\n",
+            escape_html(&item.path.to_string_lossy()),
+            escape_html(&item.rule_id),
+            item.size.unwrap_or_default(),
+            item.size_text,
+            item.time.map(|v| v.as_secs()).unwrap_or_default(),
+            item.time_text,
+        ));
+    }
+    let output = HTML_TEMPLATE
+        .replace("__TOTAL_SIZE__", &units.format(total_size))
+        .replace("__TOTAL_COUNT__", &items.len().to_string())
+        .replace("__ROWS__", &rows);
+    std::fs::write(file, output)
+        .with_context(|| format!("Failed to write html to '{}'", file.display()))?;
+    Ok(())
+}
+
+pub fn delete_all(rx: Receiver<Message>, config: &Config) -> Result<()> {
+    // buffer all matches so that they can be previewed and ordered before deleting
+    let mut items = collect_paths(rx);
+    match config.delete_order {
+        Some(DeleteOrder::Largest) => items.sort_by_key(|v| Reverse(v.size.unwrap_or_default())),
+        Some(DeleteOrder::Smallest) => items.sort_by_key(|v| v.size.unwrap_or_default()),
+        None => {}
+    }
+    let mut freed: u64 = 0;
+    let mut targets = vec![];
+    for item in items {
+        if let Some(free) = config.free {
+            if freed >= free {
+                println!("Keep {}", item.path.display());
+                continue;
+            }
+        }
+        freed += item.size.unwrap_or_default();
+        targets.push(item);
+    }
+    if targets.is_empty() {
+        println!("No targets to delete");
+        return Ok(());
+    }
+    for item in &targets {
+        for path in item.all_paths() {
+            println!("{} {}", item.size_text, path.display());
+        }
+    }
+    println!(
+        "Total: {} targets, {}",
+        targets.len(),
+        config.units.format(freed)
+    );
+    if !config.yes {
+        print!("Delete all these targets? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !matches!(input.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+        }
+    }
+    let deleter = Deleter::new(config);
+    for item in &targets {
+        deleter.spawn_item(item);
+    }
+    let failures = deleter.wait();
+    if let Some(fail_log) = &config.fail_log {
+        write_fail_log(fail_log, &config.scan_roots, &failures)?;
+    }
+    Ok(())
+}
+
+/// delete the paths listed in a fail log without searching, confined to the roots it records
+pub fn retry_from(file: &Path, config: &Config) -> Result<()> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read fail log '{}'", file.display()))?;
+    let mut config = config.clone();
+    let mut paths = vec![];
+    for line in content.lines().filter(|v| !v.is_empty()) {
+        match line.strip_prefix("root\t") {
+            Some(root) => config.confine_to_root(Path::new(root)),
+            None => paths.push(PathBuf::from(line)),
+        }
+    }
+    if config.scan_roots.is_empty() {
+        bail!(
+            "Fail log '{}' has no search root, refusing to delete its paths",
+            file.display()
+        );
+    }
+    let deleter = Deleter::new(&config);
+    for path in paths {
+        if std::fs::symlink_metadata(&path).is_ok() {
+            deleter.spawn(path);
+        }
+    }
+    let failures = deleter.wait();
+    if let Some(fail_log) = &config.fail_log {
+        write_fail_log(fail_log, &config.scan_roots, &failures)?;
+    }
+    Ok(())
+}
+
+/// delete the given targets, each must be under `entry` and purged by a rule unless `force`
+pub fn delete_targets(paths: &[PathBuf], entry: &Path, config: &Config, force: bool) -> Result<()> {
+    let root = std::fs::canonicalize(entry).unwrap_or_else(|_| entry.to_path_buf());
+    for path in paths {
+        if !path.starts_with(&root) || path == &root {
+            bail!("'{}' is not under '{}'", path.display(), root.display());
+        }
+        if !force && !is_rule_target(path, &root, config) {
+            bail!(
+                "'{}' is not matched by any rule, use --force to delete it anyway",
+                path.display()
+            );
+        }
+    }
+    let deleter = Deleter::new(config);
+    for path in paths {
+        deleter.spawn(path.clone());
+    }
+    let failures = deleter.wait();
+    if let Some(fail_log) = &config.fail_log {
+        write_fail_log(fail_log, &config.scan_roots, &failures)?;
+    }
     Ok(())
 }
 
-pub fn delete_all(rx: Receiver<Message>) -> Result<()> {
-    let wg = WaitGroup::new();
-    let pool = ThreadPool::default();
+/// print the paths read from stdin that are targets, relative paths are joined with `base`
+pub fn filter_stdin(base: &Path, entry: &Path, config: &Config) -> Result<()> {
+    let root = std::fs::canonicalize(entry).unwrap_or_else(|_| entry.to_path_buf());
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let path = base.join(&line);
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            continue;
+        };
+        let Ok(parent) = std::fs::canonicalize(parent) else {
+            continue;
+        };
+        let path = parent.join(name);
+        // the depth of the rules counts from the search root, or from the top otherwise
+        let root = if path.starts_with(&root) {
+            root.as_path()
+        } else {
+            path.ancestors().last().unwrap_or(&path)
+        };
+        if is_rule_target(&path, root, config) {
+            writeln!(stdout, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+/// whether a rule matched in one of the ancestors of `path` below `root` purges it
+fn is_rule_target(path: &Path, root: &Path, config: &Config) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root))
+        .any(|dir| {
+            let Ok(read_dir) = std::fs::read_dir(dir) else {
+                return false;
+            };
+            let names: Vec<String> = read_dir
+                .flatten()
+                .map(|v| v.file_name().to_string_lossy().to_string())
+                .collect();
+            let depth = dir.strip_prefix(root).map(|v| v.components().count() + 1);
+            let mut checker = Checker::new(config, depth.unwrap_or_default(), dir);
+            for name in &names {
+                checker.check(name);
+            }
+            checker.to_matches().iter().any(|(name, (_, purges, _))| {
+                purges
+                    .iter()
+                    .any(|purge| purge_path(&dir.join(name), purge) == path)
+            })
+        })
+}
+
+/// write a `root\t<root>` line per search root, then the failed paths one per line
+fn write_fail_log(file: &Path, roots: &[PathBuf], failures: &[PathBuf]) -> Result<()> {
+    let content: String = roots
+        .iter()
+        .map(|v| format!("root\t{}\n", v.display()))
+        .chain(failures.iter().map(|v| format!("{}\n", v.display())))
+        .collect();
+    std::fs::write(file, content)
+        .with_context(|| format!("Failed to write fail log '{}'", file.display()))
+}
+
+pub fn prompt_delete(rx: Receiver<Message>, config: &Config) -> Result<()> {
+    let items = collect_paths(rx);
+    if items.is_empty() {
+        println!("No targets found");
+        return Ok(());
+    }
+    for (i, item) in items.iter().enumerate() {
+        println!("{:>4}) {}", i + 1, item);
+    }
+    print!("Targets to delete, e.g. 1 3 5-7, 'a' for all, empty to quit: ");
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let indexes = parse_indexes(&input, items.len())?;
+    let deleter = Deleter::new(config);
+    for index in indexes {
+        deleter.spawn_item(&items[index]);
+    }
+    deleter.wait();
+    Ok(())
+}
+
+/// parse 1-based selections like `1 3 5-7` or `a` into 0-based indexes
+fn parse_indexes(input: &str, len: usize) -> Result<Vec<usize>> {
+    let input = input.trim();
+    if input == "a" {
+        return Ok((0..len).collect());
+    }
+    let mut output = vec![];
+    let parse_index = |value: &str| -> Result<usize> {
+        match value.parse::<usize>() {
+            Ok(v) if v >= 1 && v <= len => Ok(v - 1),
+            _ => bail!("Invalid selection '{}'", value),
+        }
+    };
+    for part in input.split(|c: char| c == ',' || c.is_whitespace()) {
+        if part.is_empty() {
+            continue;
+        }
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (parse_index(start)?, parse_index(end)?),
+            None => (parse_index(part)?, parse_index(part)?),
+        };
+        for index in start..=end {
+            if !output.contains(&index) {
+                output.push(index);
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn collect_paths(rx: Receiver<Message>) -> Vec<PathItem> {
+    let mut items = vec![];
     for message in rx {
         match message {
-            Message::AddPath(path) => {
-                spawn_delete_path(pool.clone(), path.path.clone(), wg.clone());
-            }
+            Message::AddPath(path) => items.push(path),
+            Message::PutError(err) => eprintln!("{err}"),
             Message::DoneSearch => break,
             _ => {}
         }
     }
-    wg.wait();
+    items
+}
+
+/// delete paths in a thread pool, failures are reported together once all deletions finish
+#[derive(Default)]
+struct Deleter {
+    pool: ThreadPool,
+    wg: WaitGroup,
+    failures: Arc<Mutex<Vec<(PathBuf, String)>>>,
+    /// deleted paths buffered to be printed in order, used with `sorted_output`
+    deleted: Option<Arc<Mutex<Vec<String>>>>,
+    config: Arc<Config>,
+}
+
+impl Deleter {
+    fn new(config: &Config) -> Self {
+        Self {
+            deleted: config.sorted_output.then(Default::default),
+            config: Arc::new(config.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// delete the paths of the item, the size is only known for an item of a single path
+    fn spawn_item(&self, item: &PathItem) {
+        let size = item.size.filter(|_| item.merged_paths.is_empty());
+        for path in item.all_paths() {
+            self.spawn_sized(path, size);
+        }
+    }
+
+    fn spawn(&self, path: PathBuf) {
+        self.spawn_sized(path, None)
+    }
+
+    fn spawn_sized(&self, path: PathBuf, size: Option<u64>) {
+        let config = self.config.clone();
+        let wg = self.wg.clone();
+        let failures = self.failures.clone();
+        let deleted = self.deleted.clone();
+        self.pool.execute(move || {
+            let start = Instant::now();
+            match remove_path(&path, &config) {
+                Ok(_) => {
+                    if let Some(summary) = &config.summary {
+                        summary.lock().unwrap().add_deleted(&path);
+                    }
+                    let line = if config.verbose {
+                        let size = size
+                            .map(|v| format!(" ({})", config.units.format(v)))
+                            .unwrap_or_default();
+                        format!(
+                            "Deleted {}{size} in {:.1}s",
+                            path.display(),
+                            start.elapsed().as_secs_f64()
+                        )
+                    } else {
+                        format!("Delete {}", path.display())
+                    };
+                    match deleted {
+                        Some(deleted) => deleted.lock().unwrap().push(line),
+                        None => println!("{line}"),
+                    }
+                }
+                Err(err) => {
+                    if let Some(summary) = &config.summary {
+                        summary.lock().unwrap().add_error();
+                    }
+                    failures.lock().unwrap().push((path, err.to_string()))
+                }
+            }
+            drop(wg);
+        });
+    }
+
+    /// wait for all deletions, returns the paths that failed to delete
+    fn wait(self) -> Vec<PathBuf> {
+        self.wg.wait();
+        if let Some(deleted) = &self.deleted {
+            let mut deleted = deleted.lock().unwrap();
+            deleted.sort();
+            for line in deleted.iter() {
+                println!("{line}");
+            }
+        }
+        let failures = self.failures.lock().unwrap();
+        if failures.is_empty() {
+            return vec![];
+        }
+        eprintln!("\n{} targets failed to delete:", failures.len());
+        for (path, err) in failures.iter() {
+            eprintln!("  {}: {}", path.display(), err);
+        }
+        failures.iter().map(|(path, _)| path.clone()).collect()
+    }
+}
+
+fn is_artifact_like<C: jwalk::ClientState>(dir_entry: &jwalk::DirEntry<C>, name: &str) -> bool {
+    dir_entry.file_type.is_dir() && ARTIFACT_NAMES.contains(&name)
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// print which entries of a single dir the rule would purge
+pub fn test_rule(rule: &str, dir: &Path) -> Result<()> {
+    let mut config = Config::default();
+    config.add_rule(rule)?;
+    let mut names = dir_names(dir)?;
+    names.sort();
+    let mut checker = Checker::new(&config, 1, dir);
+    for name in &names {
+        checker.check(name);
+    }
+    let purges = checker.to_matches();
+    let rule = &config.rules[0];
+    let matches = checker.matches.get(rule.get_id());
+    if rule.no_detect() {
+        println!("detect: not required");
+    } else {
+        let mut detects: Vec<&str> = matches
+            .map(|v| v.check.iter().copied().collect())
+            .unwrap_or_default();
+        detects.sort_unstable();
+        let satisfied = matches
+            .map(|v| rule.detects_satisfied(&v.hits))
+            .unwrap_or_default();
+        if !satisfied {
+            println!("detect: not satisfied");
+        } else {
+            println!("detect: {}", detects.join(", "));
+        }
+    }
+    for name in &names {
+        if purges.contains_key(name) {
+            println!("purge: {name}");
+        } else if matches
+            .map(|v| v.purge.contains_key(name.as_str()))
+            .unwrap_or_default()
+        {
+            println!("skip: {name}");
+        }
+    }
     Ok(())
 }
 
-fn spawn_delete_path(pool: ThreadPool, path: PathBuf, wg: WaitGroup) {
-    pool.execute(move || {
-        match remove_dir_all(&path) {
-            Ok(_) => println!("Delete {}", path.display()),
-            Err(err) => eprintln!("Failed to delete {}, {}", path.display(), err),
+/// print why the search under `entry` finds the path or not
+pub fn explain(path: &Path, entry: &Path, config: &Config) -> Result<()> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        bail!("Cannot explain '{}'", path.display());
+    };
+    let name = name.to_string_lossy().to_string();
+    let Ok(relative_path) = path.strip_prefix(entry) else {
+        println!("result: not under the search root '{}'", entry.display());
+        return Ok(());
+    };
+    if !path.exists() {
+        println!("result: does not exist");
+        return Ok(());
+    }
+    let mut skipped = None;
+    // the search does not enter excluded dirs and matched targets
+    for ancestor in relative_path.ancestors().skip(1) {
+        if ancestor.as_os_str().is_empty() {
+            break;
         }
-        drop(wg);
-    });
+        let ancestor_path = entry.join(ancestor);
+        let ancestor_name = ancestor_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        if config.exclude.iter().any(|v| v == &ancestor_name) {
+            skipped = Some(format!(
+                "inside '{}', excluded by --exclude",
+                ancestor.display()
+            ));
+        } else if config.is_path_excluded(&ancestor_path) {
+            skipped = Some(format!(
+                "inside '{}', excluded by --exclude-path",
+                ancestor.display()
+            ));
+        } else if !config.recurse_into_matches {
+            let parent = ancestor_path.parent().unwrap_or(entry);
+            if let Some(rule_id) = explain_matches(config, parent, ancestor.components().count())?
+                .remove(ancestor_name.as_ref())
+            {
+                skipped = Some(format!(
+                    "inside '{}', a target of {rule_id}",
+                    ancestor.display()
+                ));
+            }
+        }
+    }
+    if config.exclude.contains(&name) {
+        println!("excluded: by --exclude");
+    } else if config.is_path_excluded(path) {
+        println!("excluded: by --exclude-path");
+    }
+
+    let depth = relative_path.components().count();
+    let mut names = dir_names(dir)?;
+    names.sort();
+    let mut checker = Checker::new(config, depth, dir);
+    for name in &names {
+        checker.check(name);
+    }
+    let purges = checker.to_matches();
+    for rule in &config.rules {
+        let rule_id = rule.get_id();
+        let matches = checker.matches.get(rule_id);
+        let reason = if !rule.check_depth(depth) {
+            "deeper than the depth of the rule".to_string()
+        } else if !matches
+            .map(|v| v.purge.contains_key(name.as_str()))
+            .unwrap_or_default()
+        {
+            format!("'{name}' is not a target")
+        } else if !rule.no_detect()
+            && !matches
+                .map(|v| rule.detects_satisfied(&v.hits))
+                .unwrap_or_default()
+        {
+            format!("no detect file in '{}'", dir.display())
+        } else {
+            match purges.get(&name) {
+                Some((id, _, _)) if *id != rule_id => format!("matched, but {id} comes first"),
+                Some((_, _, Some(detect))) => format!("matched, detect: {detect}"),
+                _ => "matched".to_string(),
+            }
+        };
+        println!("{rule_id}: {reason}");
+    }
+
+    let result = if let Some(skipped) = skipped {
+        format!("not searched, {skipped}")
+    } else if config.exclude.contains(&name) || config.is_path_excluded(path) {
+        "not matched, excluded".to_string()
+    } else if let Some((rule_id, _, _)) = purges.get(&name) {
+        explain_filters(path, rule_id, config)
+            .map(|v| format!("matched by {rule_id}, but {v}"))
+            .unwrap_or_else(|| format!("matched by {rule_id}"))
+    } else {
+        "not matched".to_string()
+    };
+    println!("result: {result}");
+    Ok(())
+}
+
+/// the rules of the matched entries of the dir, whose entries are at the depth
+fn explain_matches(config: &Config, dir: &Path, depth: usize) -> Result<HashMap<String, String>> {
+    let names = dir_names(dir)?;
+    let mut checker = Checker::new(config, depth, dir);
+    for name in &names {
+        checker.check(name);
+    }
+    Ok(checker
+        .to_matches()
+        .into_iter()
+        .map(|(name, (rule_id, _, _))| (name, rule_id.to_string()))
+        .collect())
+}
+
+/// the time, size or files filter that the target fails
+fn explain_filters(path: &Path, rule_id: &str, config: &Config) -> Option<String> {
+    let time_filter = config.time.or_else(|| config.rule_time(rule_id));
+    if time_filter.is_some() || config.skip_recent.is_some() {
+        let time = if config.atime {
+            last_accessed(path).map(|(time, _)| time).ok()
+        } else {
+            last_modified(path).ok()
+        };
+        if let (Some((expect, order)), Some(days)) = (time_filter, time.map(duration_days)) {
+            if !compare(order, expect, days as usize) {
+                return Some(format!("its age of {days} days fails the time filter"));
+            }
+        }
+        if let Some(time) = time.filter(|v| config.is_recent(*v)) {
+            return Some(format!(
+                "it was modified {} minutes ago, within --skip-recent",
+                time.as_secs() / 60
+            ));
+        }
+    }
+    if config.size.is_none() && config.min_files.is_none() && !config.empty_dirs {
+        return None;
+    }
+    let running = AtomicBool::new(true);
+    let (size, files) = du(path, !config.no_hardlink_dedup, &running).ok()?;
+    if !compare_size(config, size) {
+        return Some(format!(
+            "its size of {} fails the size filter",
+            config.units.format(size)
+        ));
+    }
+    if let Some(min_files) = config.min_files {
+        if files < min_files {
+            return Some(format!("it has {files} files, fewer than {min_files}"));
+        }
+    }
+    if config.empty_dirs && files != 0 {
+        return Some(format!("it has {files} files, but --empty-dirs is given"));
+    }
+    None
+}
+
+fn dir_names(dir: &Path) -> Result<Vec<String>> {
+    let mut names = vec![];
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read dir '{}'", dir.display()))?
+    {
+        names.push(entry?.file_name().to_string_lossy().to_string());
+    }
+    Ok(names)
+}
+
+/// whether the size passes `--size`, an exact size matches within `--size-tolerance`
+fn compare_size(config: &Config, size: u64) -> bool {
+    match (config.size, config.size_tolerance) {
+        (None, _) => true,
+        (Some((expect, Ordering::Equal)), Some(tolerance)) => {
+            let delta = expect as u128 * tolerance as u128 / 100;
+            (size as u128).abs_diff(expect as u128) <= delta
+        }
+        (Some((expect, order)), _) => compare(order, expect, size),
+    }
 }
 
 fn compare<T: PartialOrd>(order: Ordering, expect: T, target: T) -> bool {
@@ -140,43 +1456,63 @@ fn compare<T: PartialOrd>(order: Ordering, expect: T, target: T) -> bool {
 struct Checker<'a, 'b> {
     matches: HashMap<&'a str, CheckMatches<'a, 'b>>,
     config: &'a Config,
+    depth: usize,
+    dir: &'a Path,
 }
 
 #[derive(Debug, Default)]
 struct CheckMatches<'a, 'b> {
     purge: HashMap<&'b str, &'a Vec<String>>,
     check: HashSet<&'b str>,
+    /// the detect patterns that were hit, see `Rule::detect_hits`
+    hits: HashSet<(usize, usize)>,
 }
 
 impl<'a, 'b> Checker<'a, 'b> {
-    fn new(config: &'a Config) -> Self {
+    fn new(config: &'a Config, depth: usize, dir: &'a Path) -> Self {
         Self {
             config,
             matches: Default::default(),
+            depth,
+            dir,
         }
     }
 
     fn check(&mut self, name: &'b str) {
         for rule in &self.config.rules {
+            if !rule.check_depth(self.depth) {
+                continue;
+            }
             let matches = self.matches.entry(rule.get_id()).or_default();
-            if let Some(purges) = rule.check_target(name) {
+            if let Some(purges) = rule
+                .check_target(name)
+                .or_else(|| rule.check_marker(self.dir, name))
+            {
                 matches.purge.insert(name, purges.as_ref());
             }
-            if rule.check_project(name) {
+            let hits = rule.detect_hits(self.dir, name);
+            if !hits.is_empty() {
                 matches.check.insert(name);
+                matches.hits.extend(hits);
             }
         }
     }
 
-    fn to_matches(&self) -> HashMap<String, (&'a str, &'a Vec<String>)> {
+    /// matched names with their rule, the first added rule wins when several rules match
+    fn to_matches(&self) -> HashMap<String, (&'a str, &'a Vec<String>, Option<String>)> {
         let mut output = HashMap::new();
-        for (rule_id, matches) in &self.matches {
+        for rule in &self.config.rules {
+            let rule_id = rule.get_id();
+            let Some(matches) = self.matches.get(rule_id) else {
+                continue;
+            };
             if !matches.purge.is_empty()
-                && (!matches.check.is_empty() || self.config.is_rule_no_detect(rule_id))
+                && (rule.no_detect() || rule.detects_satisfied(&matches.hits))
             {
+                let detect = matches.check.iter().min().map(|v| v.to_string());
                 for (name, purges) in &matches.purge {
                     if !output.contains_key(*name) {
-                        output.insert(name.to_string(), (*rule_id, *purges));
+                        output.insert(name.to_string(), (rule_id, *purges, detect.clone()));
                     }
                 }
             }
@@ -185,28 +1521,391 @@ impl<'a, 'b> Checker<'a, 'b> {
     }
 }
 
-fn du(path: &Path) -> Result<u64> {
+/// a process with an open file or working dir inside the dir, found by scanning `/proc`
+#[cfg(target_os = "linux")]
+fn find_in_use(dir: &Path) -> Option<u32> {
+    let dir = std::fs::canonicalize(dir).ok()?;
+    let is_inside = |link: io::Result<PathBuf>| link.map(|v| v.starts_with(&dir)).unwrap_or(false);
+    for proc_entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = proc_entry.file_name().to_str().and_then(|v| v.parse().ok()) else {
+            continue;
+        };
+        let proc_dir = proc_entry.path();
+        if is_inside(std::fs::read_link(proc_dir.join("cwd"))) {
+            return Some(pid);
+        }
+        // the fds of processes of other users can not be read
+        let Ok(fds) = std::fs::read_dir(proc_dir.join("fd")) else {
+            continue;
+        };
+        if fds
+            .flatten()
+            .any(|fd| is_inside(std::fs::read_link(fd.path())))
+        {
+            return Some(pid);
+        }
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_in_use(_dir: &Path) -> Option<u32> {
+    None
+}
+
+/// whether the path is strictly inside one of the roots, the parent is canonicalized so a
+/// symlink is checked by its own location rather than what it points to
+fn is_inside_roots(path: &Path, roots: &[PathBuf]) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    if name == ".." {
+        return false;
+    }
+    let Ok(parent) = std::fs::canonicalize(parent) else {
+        return false;
+    };
+    let path = parent.join(name);
+    roots
+        .iter()
+        .any(|root| path != *root && path.starts_with(root))
+}
+
+/// the protected path that the dir equals or contains
+fn find_protected(dir: &Path, protected_paths: &[PathBuf]) -> Option<PathBuf> {
+    let dir = std::fs::canonicalize(dir).ok()?;
+    protected_paths
+        .iter()
+        .find(|v| v.starts_with(&dir))
+        .cloned()
+}
+
+/// delete a target with the backend and safety checks of the config, used by both the TUI and
+/// the other modes
+pub fn remove_path(path: &Path, config: &Config) -> io::Result<()> {
+    let backend = config.delete_backend;
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.is_symlink() && !config.delete_symlinks {
+        return Err(io::Error::other(
+            "skipped it, it is a symlink, use --delete-symlinks to delete it",
+        ));
+    }
+    if config.scan_roots.is_empty() {
+        return Err(io::Error::other(
+            "refusing to delete it, the search root is unknown",
+        ));
+    }
+    if !is_inside_roots(path, &config.scan_roots) {
+        return Err(io::Error::other(
+            "refusing to delete it, it is outside the search root",
+        ));
+    }
+    let is_dir = metadata.is_dir();
+    // a symlink is removed by itself, so only real dirs can take a protected path with them
+    if let Some(protected) = is_dir
+        .then(|| find_protected(path, &config.protected_paths))
+        .flatten()
+    {
+        return Err(io::Error::other(format!(
+            "refusing to delete it, it contains the search root or current dir '{}'",
+            protected.display()
+        )));
+    }
+    if is_dir && config.check_in_use {
+        if let Some(pid) = find_in_use(path) {
+            return Err(io::Error::other(format!(
+                "skipped it, it is in use by process {pid}"
+            )));
+        }
+    }
+    let remove = |path: &Path| {
+        if !is_dir {
+            std::fs::remove_file(path)
+        } else if backend == DeleteBackend::Std {
+            std::fs::remove_dir_all(path)
+        } else {
+            remove_dir_all(path)
+        }
+    };
+    match backend {
+        DeleteBackend::Trash => move_to_trash(path),
+        DeleteBackend::Std => remove(path),
+        DeleteBackend::Robust => remove(path).or_else(|err| {
+            if err.kind() != io::ErrorKind::PermissionDenied {
+                return Err(err);
+            }
+            clear_readonly(path)?;
+            remove(path)
+        }),
+    }
+}
+
+/// make the path and everything inside it writable by the owner
+fn clear_readonly(path: &Path) -> io::Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() {
+        return Ok(());
+    }
+    let mut permissions = metadata.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    std::fs::set_permissions(path, permissions)?;
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            clear_readonly(&entry?.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// move the path into the freedesktop trash, or `~/.Trash` on macOS
+#[cfg(unix)]
+fn move_to_trash(path: &Path) -> io::Result<()> {
+    // a relative dir would put the trash under the current dir, likely inside the search root
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .filter(|v| v.is_absolute());
+    let no_trash = || io::Error::new(io::ErrorKind::NotFound, "no trash dir, HOME is not set");
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+    if cfg!(target_os = "macos") {
+        let files_dir = home.ok_or_else(no_trash)?.join(".Trash");
+        let target = unique_trash_path(&files_dir, name, |_| false);
+        return move_into_trash(path, &target);
+    }
+    let trash_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|v| v.is_absolute())
+        .or_else(|| home.map(|v| v.join(".local").join("share")))
+        .ok_or_else(no_trash)?
+        .join("Trash");
+    let (files_dir, info_dir) = (trash_dir.join("files"), trash_dir.join("info"));
+    std::fs::create_dir_all(&files_dir)?;
+    std::fs::create_dir_all(&info_dir)?;
+    let target = unique_trash_path(&files_dir, name, |v| {
+        info_dir
+            .join(format!("{}.trashinfo", v.to_string_lossy()))
+            .exists()
+    });
+    let target_name = target.file_name().unwrap_or(name).to_string_lossy();
+    let info_file = info_dir.join(format!("{target_name}.trashinfo"));
+    let path = path.canonicalize()?;
+    std::fs::write(
+        &info_file,
+        format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            percent_encode(&path.to_string_lossy()),
+            format_utc(SystemTime::now())
+        ),
+    )?;
+    move_into_trash(&path, &target).inspect_err(|_| {
+        let _ = std::fs::remove_file(&info_file);
+    })
+}
+
+#[cfg(not(unix))]
+fn move_to_trash(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "trash is not supported on this platform",
+    ))
+}
+
+/// move the path to the target in a trash dir, a trash on another filesystem takes a copy, which
+/// frees no space on the volume of the path until the trash is emptied
+#[cfg(unix)]
+fn move_into_trash(path: &Path, target: &Path) -> io::Result<()> {
+    let trash_dir = target.parent().unwrap_or(target);
+    if is_same_device(path, trash_dir)? {
+        return std::fs::rename(path, target);
+    }
+    eprintln!(
+        "warning: the trash '{}' is on another filesystem, trashing '{}' will not free space until the trash is emptied",
+        trash_dir.display(),
+        path.display()
+    );
+    if let Err(err) = copy_all(path, target) {
+        let _ = remove_dir_all(target).or_else(|_| std::fs::remove_file(target));
+        return Err(err);
+    }
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+#[cfg(unix)]
+fn is_same_device(path: &Path, dir: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(std::fs::symlink_metadata(path)?.dev() == std::fs::metadata(dir)?.dev())
+}
+
+/// copy a file, symlink or dir with everything inside it
+#[cfg(unix)]
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+    } else if metadata.is_dir() {
+        std::fs::create_dir(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        std::fs::set_permissions(to, metadata.permissions())
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// a path in the dir named after the file that is not taken yet
+#[cfg(unix)]
+fn unique_trash_path(
+    dir: &Path,
+    name: &std::ffi::OsStr,
+    taken: impl Fn(&std::ffi::OsStr) -> bool,
+) -> PathBuf {
+    let mut candidate = name.to_os_string();
+    let mut index = 1;
+    while dir.join(&candidate).exists() || taken(&candidate) {
+        candidate = name.to_os_string();
+        candidate.push(format!(".{index}"));
+        index += 1;
+    }
+    dir.join(candidate)
+}
+
+/// percent-encode a path as required by `.trashinfo` files
+#[cfg(unix)]
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// format the time as `YYYY-MM-DDThh:mm:ss` in UTC
+fn format_utc(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or_default();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// size of a target computed on demand, see `--lazy-size`
+pub fn lazy_size(path: &Path, dedup: bool) -> Option<u64> {
+    du(path, dedup, &AtomicBool::new(true))
+        .ok()
+        .map(|(size, _)| size)
+}
+
+/// total size and number of files of the path, hard linked files are counted once when `dedup`
+/// is set, fails once `running` is cleared
+fn du(path: &Path, dedup: bool, running: &AtomicBool) -> Result<(u64, usize)> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((metadata.len(), 1));
+    }
     let mut total: u64 = 0;
+    let mut files = 0;
+    let mut seen: HashSet<(u64, u64)> = HashSet::new();
 
-    for dir_entry_result in WalkDirGeneric::<((), Option<u64>)>::new(path)
+    for dir_entry_result in WalkDirGeneric::<((), Option<(u64, Option<(u64, u64)>)>)>::new(path)
         .skip_hidden(false)
-        .process_read_dir(|_, _, _, dir_entry_results| {
+        .process_read_dir(move |_, _, _, dir_entry_results| {
             dir_entry_results.iter_mut().for_each(|dir_entry_result| {
                 if let Ok(dir_entry) = dir_entry_result {
                     if !dir_entry.file_type.is_dir() {
-                        dir_entry.client_state =
-                            Some(dir_entry.metadata().map(|m| m.len()).unwrap_or_default());
+                        dir_entry.client_state = Some(
+                            dir_entry
+                                .metadata()
+                                .map(|m| (m.len(), dedup.then(|| hardlink_id(&m)).flatten()))
+                                .unwrap_or_default(),
+                        );
                     }
                 }
             })
         })
     {
+        if !running.load(atomic::Ordering::SeqCst) {
+            bail!("Size computation of '{}' was cancelled", path.display());
+        }
         let dir_entry = dir_entry_result?;
-        if let Some(len) = &dir_entry.client_state {
-            total += len;
+        if let Some((len, id)) = &dir_entry.client_state {
+            if id.map(|id| seen.insert(id)).unwrap_or(true) {
+                total += len;
+                files += 1;
+            }
         }
     }
-    Ok(total)
+    Ok((total, files))
+}
+
+/// device and inode of a file that has other hard links
+#[cfg(unix)]
+fn hardlink_id(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn hardlink_id(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// mount points and filesystem types from `/proc/mounts`, empty on other platforms
+fn read_mounts() -> Vec<(PathBuf, String)> {
+    let Ok(content) = std::fs::read_to_string("/proc/mounts") else {
+        return vec![];
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace().skip(1);
+            let mount_point = parts.next()?.replace("\\040", " ");
+            let fs_type = parts.next()?;
+            Some((PathBuf::from(mount_point), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// filesystem type of the deepest mount point containing the path
+fn mount_fs_type(mounts: &[(PathBuf, String)], path: &Path) -> Option<String> {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, fs_type)| fs_type.clone())
 }
 
 fn last_modified(path: &Path) -> Result<Duration> {
@@ -217,6 +1916,18 @@ fn last_modified(path: &Path) -> Result<Duration> {
     Ok(output)
 }
 
+/// time since last access, and whether the access time equals the modification time
+fn last_accessed(path: &Path) -> Result<(Duration, bool)> {
+    let metdata = std::fs::metadata(path)?;
+    let accessed = metdata.accessed()?;
+    let stale = metdata
+        .modified()
+        .map(|v| v == accessed)
+        .unwrap_or_default();
+    let output = SystemTime::now().duration_since(accessed)?;
+    Ok((output, stale))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -229,7 +1940,7 @@ mod tests {
             let mut config = Config::default();
             let ret = config.add_rule($id);
             assert!(ret.is_ok());
-            let mut checker = Checker::new(&config);
+            let mut checker = Checker::new(&config, 1, Path::new("."));
             for name in $names {
                 checker.check(name);
             }
@@ -239,10 +1950,182 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_parse_indexes() {
+        assert_eq!(parse_indexes("1 3", 5).unwrap(), vec![0, 2]);
+        assert_eq!(parse_indexes("2-4,1\n", 5).unwrap(), vec![1, 2, 3, 0]);
+        assert_eq!(parse_indexes("a", 3).unwrap(), vec![0, 1, 2]);
+        assert!(parse_indexes("", 3).unwrap().is_empty());
+        assert!(parse_indexes("0", 3).is_err());
+        assert!(parse_indexes("4", 3).is_err());
+    }
+
     #[test]
     fn test_match_paths() {
         assert_match_paths!("target@Cargo.toml", &["target", "Cargo.toml"], &["target"]);
         assert_match_paths!("target@Cargo.toml", &["target.rs", "Cargo.toml"]);
         assert_match_paths!("Debug,Release@*.sln", &["Debug", "Demo.sln"], &["Debug"]);
+        assert_match_paths!("node_modules:1", &["node_modules"], &["node_modules"]);
+        assert_match_paths!("node_modules:0", &["node_modules"]);
+    }
+
+    #[test]
+    fn test_mount_fs_type() {
+        let mounts = vec![
+            (PathBuf::from("/"), "overlay".to_string()),
+            (PathBuf::from("/tmp"), "tmpfs".to_string()),
+        ];
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/tmp/app/target")),
+            Some("tmpfs".to_string())
+        );
+        assert_eq!(
+            mount_fs_type(&mounts, Path::new("/tmpdir/target")),
+            Some("overlay".to_string())
+        );
+        assert_eq!(mount_fs_type(&[], Path::new("/tmp")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_du_hardlink() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(dir.join("a"), "hello").unwrap();
+        std::fs::hard_link(dir.join("a"), dir.join("b")).unwrap();
+        let running = AtomicBool::new(true);
+        assert_eq!(du(&dir, true, &running).unwrap(), (5, 1));
+        assert_eq!(du(&dir, false, &running).unwrap(), (10, 2));
+        running.store(false, atomic::Ordering::SeqCst);
+        assert!(du(&dir, true, &running).is_err());
+    }
+
+    #[test]
+    fn test_match_rule_order() {
+        for (rules, winner) in [
+            (["target@Cargo.toml", "target"], "target@Cargo.toml"),
+            (["target", "target@Cargo.toml"], "target"),
+        ] {
+            let mut config = Config::default();
+            for rule in rules {
+                config.add_rule(rule).unwrap();
+            }
+            let mut checker = Checker::new(&config, 1, Path::new("."));
+            checker.check("target");
+            checker.check("Cargo.toml");
+            assert_eq!(checker.to_matches()["target"].0, winner);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_trash_info() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661);
+        assert_eq!(format_utc(time), "2000-02-29T01:01:01");
+        assert_eq!(percent_encode("/a b/ü"), "/a%20b/%C3%BC");
+    }
+
+    #[test]
+    fn test_compare_size() {
+        let mut config = Config::default();
+        config.set_size("=100M").unwrap();
+        let mb = 1024 * 1024;
+        assert!(compare_size(&config, 100 * mb));
+        assert!(!compare_size(&config, 104 * mb));
+        config.set_size_tolerance("5").unwrap();
+        assert!(compare_size(&config, 104 * mb));
+        assert!(compare_size(&config, 96 * mb));
+        assert!(!compare_size(&config, 106 * mb));
+        assert!(!compare_size(&config, 94 * mb));
+        config.set_size("+100M").unwrap();
+        assert!(!compare_size(&config, 99 * mb));
+        assert!(config.set_size_tolerance("101").is_err());
+    }
+
+    #[test]
+    fn test_purge_path() {
+        let dir = Path::new("app").join("project");
+        assert_eq!(purge_path(&dir, "project/target"), dir.join("target"));
+        assert_eq!(purge_path(&dir, "proj*/target/"), dir.join("target"));
+        assert_eq!(purge_path(&dir, "project"), dir);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_same_device() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.join("a");
+        std::fs::write(&path, "").unwrap();
+        assert!(is_same_device(&path, &dir).unwrap());
+        assert!(!is_same_device(&path, Path::new("/proc")).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_all() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let from = dir.join("from");
+        std::fs::create_dir_all(from.join("sub")).unwrap();
+        std::fs::write(from.join("sub").join("a"), "a").unwrap();
+        std::os::unix::fs::symlink("sub/a", from.join("link")).unwrap();
+        copy_all(&from, &dir.join("to")).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("to/sub/a")).unwrap(), "a");
+        assert_eq!(
+            std::fs::read_link(dir.join("to/link")).unwrap(),
+            Path::new("sub/a")
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_find_in_use() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let target = dir.join("target");
+        std::fs::create_dir(&target).unwrap();
+        let path = target.join("a");
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(find_in_use(&target), None);
+        let file = std::fs::File::open(&path).unwrap();
+        assert_eq!(find_in_use(&target), Some(std::process::id()));
+        drop(file);
+        assert_eq!(find_in_use(&target), None);
+    }
+
+    #[test]
+    fn test_remove_path_protected() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let target = dir.join("target");
+        std::fs::create_dir_all(target.join("src")).unwrap();
+        let mut config = Config::default();
+        config.confine_to_root(&dir);
+        config.protect_path(&target.join("src"));
+        assert!(remove_path(&target, &config).is_err());
+        assert!(target.exists());
+        config.protected_paths.clear();
+        assert!(remove_path(&target, &config).is_ok());
+        assert!(!target.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_inside_roots() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let root = dir.join("root");
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::create_dir_all(outside.join("target")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        let roots = vec![std::fs::canonicalize(&root).unwrap()];
+        assert!(is_inside_roots(&root.join("target"), &roots));
+        assert!(is_inside_roots(&root.join("link"), &roots));
+        assert!(!is_inside_roots(&root, &roots));
+        assert!(!is_inside_roots(&root.join("link/target"), &roots));
+        assert!(!is_inside_roots(&root.join("target/../../outside"), &roots));
+        assert!(!is_inside_roots(&root.join("target/.."), &roots));
+
+        let mut config = Config::default();
+        config.confine_to_root(&root);
+        assert!(remove_path(&outside.join("target"), &config).is_err());
+        assert!(outside.join("target").exists());
+        assert!(remove_path(&root.join("target"), &config).is_ok());
     }
 }
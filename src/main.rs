@@ -1,52 +1,42 @@
 mod app;
 mod common;
+mod error;
 mod fs;
+mod settings;
 
 use std::{
     env,
     fs::canonicalize,
-    path::{Path, PathBuf},
+    io::{stdout, IsTerminal},
+    path::{Component, Path, PathBuf},
     process,
     sync::{
         atomic::{AtomicBool, Ordering},
-        mpsc::channel,
-        Arc,
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
     },
     thread,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
-use clap::{Arg, ArgAction, Command};
+use clap::{parser::ValueSource, Arg, ArgAction, Command};
 
 use app::run;
-use fs::{delete_all, ls, search};
+use error::ProjcleanError;
+use fs::{
+    delete_all, delete_targets, diff, explain, filter_stdin, histogram, html, keep_recent,
+    lazy_size, ls, prompt_delete, remove_path, retry_from, save_snapshot, search_roots,
+    size_percent, test_rule, tree, unknown, watch,
+};
+use settings::{Profile, RuleUsage, Settings};
 
-use common::{human_readable_folder_size, Config, Message, PathItem, PathState};
+use common::{
+    duration_days, expand_path, ColorMode, Config, ConfigValue, DeleteBackend, DeleteOrder,
+    Message, PathItem, PathState, RuleDefinition, ScanStats, SizeUnits, Summary, TruncateStyle,
+    RULES,
+};
 use inquire::{formatter::MultiOptionFormatter, MultiSelect};
 
-const RULES: [(&str, &str); 20] = [
-    ("nodejs", "node_modules"),
-    ("cargo", "target@Cargo.toml"),
-    ("maven", "target@pom.xml"),
-    ("gradle", ".gradle,build@build.gradle,build.gradle.kts"),
-    ("cmake", "build@CMakeLists.txt"),
-    ("composer", "vendor@composer.json"),
-    ("dotnet", "bin,obj@*.csproj,*.fsproj"),
-    ("vs", ".vs,Debug,Release@*.sln"),
-    ("vc++", "Debug,Release@*.vcxproj"),
-    ("swift", ".build,.swiftpm@Package.swift"),
-    ("pod", "Pods@Podfile"),
-    ("pub", ".dart_tool,build@pubspec.yaml"),
-    ("sbt", "target,project/target@build.sbt"),
-    ("jupyter", ".ipynb_checkpoints@*.ipynb"),
-    ("zig", "zig-cache,zig-out@build.zig"),
-    ("rebar", "_build@rebar.config"),
-    ("dune", "_build@dune-project"),
-    ("mix", "_build@mix.exs"),
-    ("stack", ".stack-work@stack.yaml"),
-    ("godot", ".godot@project.godot"),
-];
-
 fn main() {
     let running = Arc::new(AtomicBool::new(true));
     let running_cloned = running.clone();
@@ -64,24 +54,182 @@ fn main() {
 fn start(running: Arc<AtomicBool>) -> Result<()> {
     let matches = command().get_matches();
 
-    let config = init_config(&matches)?;
+    if let Some(rule) = matches.get_one::<String>("test-rule") {
+        let dir = matches
+            .get_one::<String>("against")
+            .map(|v| v.as_str())
+            .unwrap_or(".");
+        return test_rule(rule, Path::new(dir));
+    }
+
+    let settings = Settings::load().map_err(|err| match err {
+        ProjcleanError::Io(err) => anyhow!("Failed to read config file, {err}"),
+        err => err.into(),
+    })?;
+
+    let mut config = init_config(&matches, &settings)?;
+
+    if matches.get_flag("dump-config") {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
+
+    let summary = matches
+        .get_flag("json-summary")
+        .then(|| Arc::new(Mutex::new(Summary::default())));
+    config.summary = summary.clone();
+
+    let scan_stats = matches
+        .get_flag("stats")
+        .then(|| Arc::new(ScanStats::default()));
+    config.scan_stats = scan_stats.clone();
+
+    consume(&matches, &settings, config, running)?;
 
-    let entry = set_working_dir(&matches)?;
+    if let Some(stats) = scan_stats {
+        eprint!("{}", stats.to_text());
+    }
+
+    if let Some(summary) = summary {
+        println!("{}", summary.lock().unwrap().to_json());
+    }
+    Ok(())
+}
+
+fn consume(
+    matches: &clap::ArgMatches,
+    settings: &Settings,
+    mut config: Config,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    if let Ok(current_dir) = env::current_dir() {
+        config.protect_path(&current_dir);
+    }
+
+    if let Some(file) = matches.get_one::<String>("retry-from") {
+        return retry_from(Path::new(file), &config);
+    }
+
+    // resolved before `set_working_dir` changes the current dir
+    let targets = match matches.get_many::<String>("delete") {
+        Some(paths) => Some(paths.map(|v| resolve_path(v)).collect::<Result<Vec<_>>>()?),
+        None => None,
+    };
+
+    // paths read with `--filter` are relative to the current dir
+    let base = env::current_dir()?;
+
+    let explain_path = matches
+        .get_one::<String>("explain")
+        .map(|v| resolve_path(v))
+        .transpose()?;
+
+    let roots = match matches.get_one::<String>("cwd").filter(|v| is_glob(v)) {
+        Some(pattern) => glob_roots(pattern)?,
+        None => vec![set_working_dir(matches, settings)?],
+    };
+    roots.iter().for_each(|v| config.protect_path(v));
+    if config.load_session.is_none() {
+        // a session is confined to the roots it was saved with
+        roots.iter().for_each(|v| config.confine_to_root(v));
+    }
+    if roots.len() > 1
+        && (explain_path.is_some()
+            || matches.get_flag("filter")
+            || targets.is_some()
+            || config.watch)
+    {
+        bail!("A '--cwd' glob matching several directories cannot be used with --explain, --filter, --delete or --watch");
+    }
+    let entry = roots[0].clone();
+
+    if let Some(path) = explain_path {
+        return explain(&path, &entry, &config);
+    }
+
+    if matches.get_flag("filter") {
+        return filter_stdin(&base, &entry, &config);
+    }
+
+    if let Some(targets) = targets {
+        return delete_targets(&targets, &entry, &config, matches.get_flag("force"));
+    }
+
+    if matches.get_flag("low-priority") {
+        lower_priority();
+    }
 
     let (tx, rx) = channel();
+    if config.load_session.is_some() {
+        return Ok(run(rx, tx, &config, Arc::default())?);
+    }
     let tx2 = tx.clone();
 
-    thread::spawn(move || search(entry, config, tx2, running));
+    let config_cloned = config.clone();
+    let paused = Arc::new(AtomicBool::new(false));
+    let paused_cloned = paused.clone();
+    if config.watch {
+        thread::spawn(move || watch(entry, config_cloned, tx2, running, paused_cloned));
+    } else {
+        thread::spawn(move || search_roots(roots, config_cloned, tx2, running, paused_cloned));
+    }
+    let rx = match config.keep_recent {
+        Some(n) => keep_recent(rx, n),
+        None => rx,
+    };
+    let rx = match config.size_percent {
+        Some(percent) => size_percent(rx, percent),
+        None => rx,
+    };
+    let rx = match config.summary.clone() {
+        Some(summary) => count_messages(rx, summary),
+        None => rx,
+    };
     if matches.get_flag("delete-all") {
-        delete_all(rx)?;
+        delete_all(rx, &config)?;
+    } else if let Some(file) = matches.get_one::<String>("html") {
+        html(rx, Path::new(file), config.units)?;
+    } else if let Some(file) = matches.get_one::<String>("save-snapshot") {
+        save_snapshot(rx, Path::new(file))?;
+    } else if let Some(file) = matches.get_one::<String>("diff") {
+        diff(rx, Path::new(file), config.units)?;
+    } else if matches.get_flag("unknown") {
+        unknown(rx)?;
+    } else if matches.get_flag("histogram") {
+        histogram(rx, config.units)?;
+    } else if matches.get_flag("tree") {
+        tree(rx)?;
     } else if matches.get_flag("print") {
-        ls(rx)?;
+        ls(rx, &config)?;
+    } else if matches.get_flag("prompt") || !is_tui_supported() {
+        prompt_delete(rx, &config)?;
     } else {
-        run(rx, tx)?;
+        run(rx, tx, &config, paused)?;
     }
     Ok(())
 }
 
+/// forward the messages, counting them into the summary
+fn count_messages(rx: Receiver<Message>, summary: Arc<Mutex<Summary>>) -> Receiver<Message> {
+    let (tx, output) = channel();
+    thread::spawn(move || {
+        for message in rx {
+            let mut summary = summary.lock().unwrap();
+            match &message {
+                Message::AddPath(item) => summary.add_found(item),
+                Message::SyncPaths(items) => summary.sync_found(items),
+                Message::SetPathDeleted(path) => summary.add_deleted(path),
+                _ => {}
+            }
+            drop(summary);
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    output
+}
+
 fn command() -> Command {
     Command::new(env!("CARGO_CRATE_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
@@ -91,9 +239,14 @@ fn command() -> Command {
                 .short('C')
                 .long("cwd")
                 .value_name("DIR")
-                .default_value(".")
                 .action(ArgAction::Set)
-                .help("Start searching from <DIR>"),
+                .help("Start searching from <DIR>, a glob searches every matching dir [default: .]"),
+        )
+        .arg(
+            Arg::new("logical-paths")
+                .long("logical-paths")
+                .action(ArgAction::SetTrue)
+                .help("Report paths under <DIR> as given, without resolving symlinks"),
         )
         .arg(
             Arg::new("exclude")
@@ -104,30 +257,319 @@ fn command() -> Command {
                 .action(ArgAction::Append)
                 .help("Exclude directories from search, e.g. ignore1,ignore2"),
         )
+        .arg(
+            Arg::new("exclude-git")
+                .long("exclude-git")
+                .action(ArgAction::SetTrue)
+                .help("Exclude .git directories from search, same as --exclude .git"),
+        )
+        .arg(
+            Arg::new("exclude-path")
+                .long("exclude-path")
+                .value_name("PREFIX")
+                .action(ArgAction::Append)
+                .help("Exclude directories whose absolute path starts with <PREFIX>"),
+        )
         .arg(
             Arg::new("time")
                 .short('t')
                 .long("time")
-                .value_name("[+|-]DAY")
+                .value_name("[+|-|=]DAY")
                 .allow_hyphen_values(true)
                 .action(ArgAction::Set)
                 .help("Path was last modified less than, more than or exactly <DAY> days"),
         )
+        .arg(
+            Arg::new("skip-recent")
+                .long("skip-recent")
+                .value_name("MINUTES")
+                .action(ArgAction::Set)
+                .help("Skip targets modified within the last <MINUTES> minutes"),
+        )
+        .arg(
+            Arg::new("atime")
+                .long("atime")
+                .action(ArgAction::SetTrue)
+                .help("Use last access time instead of last modification time with --time"),
+        )
+        .arg(
+            Arg::new("fs-info")
+                .long("fs-info")
+                .action(ArgAction::SetTrue)
+                .help("Annotate targets with their filesystem type, Linux only"),
+        )
+        .arg(
+            Arg::new("no-hardlink-dedup")
+                .long("no-hardlink-dedup")
+                .action(ArgAction::SetTrue)
+                .help("Count hard linked files every time they appear"),
+        )
+        .arg(
+            Arg::new("no-size")
+                .long("no-size")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("size")
+                .help("Skip computing the sizes of targets"),
+        )
+        .arg(
+            Arg::new("lazy-size")
+                .long("lazy-size")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "size",
+                    "no-size",
+                    "print",
+                    "delete-all",
+                    "delete-order",
+                    "free",
+                    "prompt",
+                    "histogram",
+                    "tree",
+                    "top",
+                    "html",
+                    "save-snapshot",
+                    "diff",
+                ])
+                .help("Compute the sizes of targets only once they are shown, TUI only"),
+        )
         .arg(
             Arg::new("size")
                 .short('s')
                 .long("size")
-                .value_name("[+|-]SIZE")
+                .value_name("[+|-|=]SIZE")
                 .allow_hyphen_values(true)
                 .action(ArgAction::Set)
                 .help("Path uses less than, more than or exactly <SIZE> units (K|M|G|T) of space"),
         )
+        .arg(
+            Arg::new("size-tolerance")
+                .long("size-tolerance")
+                .value_name("PERCENT")
+                .requires("size")
+                .help("Match sizes within <PERCENT> of an exact --size, e.g. --size 100M --size-tolerance 5"),
+        )
+        .arg(
+            Arg::new("min-files")
+                .long("min-files")
+                .value_name("N")
+                .help("Skip targets that contain fewer than <N> files"),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .action(ArgAction::SetTrue)
+                .help("Print a line with the search root and date before the results, used with --print"),
+        )
+        .arg(
+            Arg::new("separator")
+                .long("separator")
+                .value_name("STR")
+                .action(ArgAction::Set)
+                .help("Print <STR> after each result instead of a newline, e.g. '\\0', used with --print"),
+        )
+        .arg(
+            Arg::new("empty-dirs")
+                .long("empty-dirs")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("min-files")
+                .help("Only report the targets that contain no files"),
+        )
+        .arg(
+            Arg::new("recurse-into-matches")
+                .long("recurse-into-matches")
+                .action(ArgAction::SetTrue)
+                .help("Also search inside the targets for the targets of other rules, slower as every target is walked"),
+        )
+        .arg(
+            Arg::new("keep-recent")
+                .long("keep-recent")
+                .value_name("N")
+                .conflicts_with("watch")
+                .help("Skip the <N> most recently modified targets of each rule"),
+        )
+        .arg(
+            Arg::new("size-percent")
+                .long("size-percent")
+                .value_name("PERCENT")
+                .conflicts_with_all(["watch", "lazy-size", "no-size"])
+                .help("Only report the targets taking at least <PERCENT> of the total size"),
+        )
+        .arg(
+            Arg::new("units")
+                .long("units")
+                .value_name("UNITS")
+                .value_parser(["short", "iec", "si", "bytes"])
+                .action(ArgAction::Set)
+                .help("Units to print sizes with [default: short]"),
+        )
+        .arg(
+            Arg::new("bytes")
+                .long("bytes")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("units")
+                .help("Print sizes in exact bytes, same as --units bytes"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .action(ArgAction::Set)
+                .help("Stop searching after <SECONDS> seconds"),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .value_name("GLOB")
+                .action(ArgAction::Append)
+                .help("Treat matching directories as archives and skip their targets"),
+        )
+        .arg(
+            Arg::new("include-archived")
+                .long("include-archived")
+                .action(ArgAction::SetTrue)
+                .help("Include the targets inside archives, marked as archived"),
+        )
+        .arg(
+            Arg::new("delete-symlinks")
+                .long("delete-symlinks")
+                .action(ArgAction::SetTrue)
+                .help("Delete the targets that are symlinks, only the links are removed"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .action(ArgAction::SetTrue)
+                .help("Print diagnostics of the search to stderr when done"),
+        )
+        .arg(
+            Arg::new("check-in-use")
+                .long("check-in-use")
+                .action(ArgAction::SetTrue)
+                .help("Skip deleting targets that a process has open files in, Linux only"),
+        )
+        .arg(
+            Arg::new("low-priority")
+                .long("low-priority")
+                .action(ArgAction::SetTrue)
+                .help("Lower the CPU and IO priority of the search"),
+        )
+        .arg(
+            Arg::new("max-results")
+                .long("max-results")
+                .value_name("N")
+                .action(ArgAction::Set)
+                .help("Stop searching after <N> targets are found"),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .action(ArgAction::SetTrue)
+                .help("Keep re-scanning and update the found targets, used with --print or the TUI"),
+        )
+        .arg(
+            Arg::new("per-project")
+                .long("per-project")
+                .action(ArgAction::SetTrue)
+                .help("Merge the targets of a project into one item"),
+        )
         .arg(
             Arg::new("delete-all")
                 .short('D')
                 .long("delete-all")
                 .action(ArgAction::SetTrue)
-                .help("Automatically delete all found targets"),
+                .help("Delete all found targets after confirmation"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .action(ArgAction::SetTrue)
+                .help("Print the size and the time taken by each deletion"),
+        )
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(ArgAction::SetTrue)
+                .help("Do not ask for confirmation, used with --delete-all"),
+        )
+        .arg(
+            Arg::new("delete-order")
+                .long("delete-order")
+                .value_name("ORDER")
+                .value_parser(["largest", "smallest"])
+                .action(ArgAction::Set)
+                .help("Delete targets in order of size, used with --delete-all"),
+        )
+        .arg(
+            Arg::new("delete-backend")
+                .long("delete-backend")
+                .value_name("BACKEND")
+                .value_parser(["std", "robust", "trash"])
+                .action(ArgAction::Set)
+                .help("How to delete targets [default: robust]"),
+        )
+        .arg(
+            Arg::new("truncate")
+                .long("truncate")
+                .value_name("PART")
+                .value_parser(["head", "middle", "tail"])
+                .action(ArgAction::Set)
+                .help("Part of long paths to cut in the TUI [default: middle]"),
+        )
+        .arg(
+            Arg::new("delete")
+                .long("delete")
+                .value_name("PATH")
+                .num_args(1..)
+                .conflicts_with("delete-all")
+                .help("Delete the given targets without searching, each must be matched by a rule"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .action(ArgAction::Set)
+                .help("Use a profile from the config file [default: default]"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .action(ArgAction::SetTrue)
+                .requires("delete")
+                .help("Delete the targets not matched by any rule, used with --delete"),
+        )
+        .arg(
+            Arg::new("fail-log")
+                .long("fail-log")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .help("Write the paths that failed to delete to <FILE>, used with --delete-all"),
+        )
+        .arg(
+            Arg::new("retry-from")
+                .long("retry-from")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .help("Delete the paths listed in a fail log without searching"),
+        )
+        .arg(
+            Arg::new("free")
+                .long("free")
+                .value_name("SIZE")
+                .action(ArgAction::Set)
+                .help("Stop deleting once <SIZE> units (K|M|G|T) of space are freed, used with --delete-all"),
+        )
+        .arg(
+            Arg::new("sorted-output")
+                .long("sorted-output")
+                .action(ArgAction::SetTrue)
+                .help("Print deleted targets sorted by path once all deletions finish, used with --delete-all"),
+        )
+        .arg(
+            Arg::new("prompt")
+                .long("prompt")
+                .action(ArgAction::SetTrue)
+                .help("List the found targets numbered and delete the chosen ones, without the TUI"),
         )
         .arg(
             Arg::new("print")
@@ -136,6 +578,201 @@ fn command() -> Command {
                 .action(ArgAction::SetTrue)
                 .help("Print the found targets"),
         )
+        .arg(
+            Arg::new("histogram")
+                .long("histogram")
+                .action(ArgAction::SetTrue)
+                .help("Print the number of found targets by size"),
+        )
+        .arg(
+            Arg::new("tree")
+                .long("tree")
+                .action(ArgAction::SetTrue)
+                .help("Print the found targets as a tree"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("TEMPLATE")
+                .action(ArgAction::Set)
+                .help("Print each target with <TEMPLATE>, placeholders: {path} {relative_path} {rule} {rule_name} {size} {age} {days} {detect}"),
+        )
+        .arg(
+            Arg::new("relative")
+                .long("relative")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("absolute")
+                .help("Print paths relative to <DIR>, used with --print"),
+        )
+        .arg(
+            Arg::new("absolute")
+                .long("absolute")
+                .action(ArgAction::SetTrue)
+                .help("Print absolute paths, used with --print [default]"),
+        )
+        .arg(
+            Arg::new("strip-prefix")
+                .long("strip-prefix")
+                .value_name("PATH")
+                .conflicts_with("relative")
+                .help("Remove <PATH> from the start of printed paths, used with --print"),
+        )
+        .arg(
+            Arg::new("top")
+                .long("top")
+                .value_name("N")
+                .action(ArgAction::Set)
+                .help("Print only the <N> largest targets, used with --print"),
+        )
+        .arg(
+            Arg::new("html")
+                .long("html")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .help("Write the found targets to a html report"),
+        )
+        .arg(
+            Arg::new("save-snapshot")
+                .long("save-snapshot")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .help("Print the found targets and save their sizes to <FILE>"),
+        )
+        .arg(
+            Arg::new("save-session")
+                .long("save-session")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .help("Save the targets left in the TUI to <FILE> on exit"),
+        )
+        .arg(
+            Arg::new("load-session")
+                .long("load-session")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .conflicts_with_all([
+                    "print",
+                    "prompt",
+                    "delete-all",
+                    "delete",
+                    "watch",
+                    "html",
+                    "save-snapshot",
+                    "diff",
+                    "unknown",
+                    "histogram",
+                    "tree",
+                    "retry-from",
+                ])
+                .help("Show the targets saved by --save-session in the TUI instead of searching"),
+        )
+        .arg(
+            Arg::new("diff")
+                .long("diff")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .help("Compare the found targets with a snapshot saved by --save-snapshot"),
+        )
+        .arg(
+            Arg::new("unknown")
+                .long("unknown")
+                .action(ArgAction::SetTrue)
+                .help("Print artifact-like directories that are not matched by any rule"),
+        )
+        .arg(
+            Arg::new("max-items")
+                .long("max-items")
+                .value_name("N")
+                .action(ArgAction::Set)
+                .help("Keep at most <N> targets in the TUI"),
+        )
+        .arg(
+            Arg::new("no-wrap")
+                .long("no-wrap")
+                .action(ArgAction::SetTrue)
+                .help("Stop list navigation at the first/last item instead of wrapping around"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .help("When to use colors and other escape sequences"),
+        )
+        .arg(
+            Arg::new("mouse")
+                .long("mouse")
+                .action(ArgAction::SetTrue)
+                .help("Scroll and select with the mouse in the TUI, this disables the native text selection"),
+        )
+        .arg(
+            Arg::new("age-color")
+                .long("age-color")
+                .action(ArgAction::SetTrue)
+                .help("Color the last modified time by age in the TUI"),
+        )
+        .arg(
+            Arg::new("group-deleted")
+                .long("group-deleted")
+                .action(ArgAction::SetTrue)
+                .help("Move deleted items into a separate panel in the TUI"),
+        )
+        .arg(
+            Arg::new("rules-file")
+                .long("rules-file")
+                .value_name("FILE")
+                .action(ArgAction::Set)
+                .help("Load rule definitions from <FILE> into the rule picker"),
+        )
+        .arg(
+            Arg::new("sort-rules-by-usage")
+                .long("sort-rules-by-usage")
+                .action(ArgAction::SetTrue)
+                .help("List the most often selected rules first in the rule picker"),
+        )
+        .arg(
+            Arg::new("json-summary")
+                .long("json-summary")
+                .action(ArgAction::SetTrue)
+                .help("Print the counts of found and deleted targets as JSON on the last line"),
+        )
+        .arg(
+            Arg::new("dump-config")
+                .long("dump-config")
+                .action(ArgAction::SetTrue)
+                .help("Print the effective configuration as TOML and exit"),
+        )
+        .arg(
+            Arg::new("test-rule")
+                .long("test-rule")
+                .value_name("RULE")
+                .action(ArgAction::Set)
+                .help("Print which entries of a single directory <RULE> would purge"),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .value_name("PATH")
+                .action(ArgAction::Set)
+                .conflicts_with_all(["delete", "retry-from", "load-session"])
+                .help("Print why the search finds <PATH> or not, checking it against every rule"),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["delete", "explain", "retry-from", "load-session"])
+                .help("Print the paths read from stdin that are targets, without searching"),
+        )
+        .arg(
+            Arg::new("against")
+                .long("against")
+                .value_name("DIR")
+                .requires("test-rule")
+                .action(ArgAction::Set)
+                .help("Directory to test the rule against [default: .]"),
+        )
         .arg(
             Arg::new("rules")
                 .help("Search rules, e.g. node_modules target@Cargo.toml")
@@ -144,84 +781,388 @@ fn command() -> Command {
         )
 }
 
-fn init_config(matches: &clap::ArgMatches) -> Result<Config> {
+fn init_config(matches: &clap::ArgMatches, settings: &Settings) -> Result<Config> {
     let mut config = Config::default();
+    let profile = settings
+        .profile(matches.get_one::<String>("profile").map(|v| v.as_str()))?
+        .cloned()
+        .unwrap_or_default();
 
-    let rules = if let Some(values) = matches.get_many::<String>("rules") {
-        values.cloned().collect()
-    } else {
-        select_rules()?
-    };
+    if let Some(values) = matches.get_many::<String>("rules") {
+        for rule in values {
+            config.add_rule(rule)?;
+        }
+    } else if !profile.rules.is_empty() {
+        for rule in &profile.rules {
+            config.add_rule(rule)?;
+        }
+    } else if let Some(rules) = settings
+        .option("rules")
+        .filter(|v| !matches!(v, ConfigValue::List(v) if v.is_empty()))
+    {
+        config.set_entry("rules", rules.clone())?;
+    } else if matches.contains_id("delete")
+        || matches.contains_id("explain")
+        || matches.get_flag("filter")
+    {
+        // check the paths against the built-in rules
+        for (name, rule) in RULES {
+            config.add_rule_definition(&RuleDefinition::new(name, rule))?;
+        }
+    } else if !matches.contains_id("retry-from")
+        && !matches.contains_id("load-session")
+        && !matches.get_flag("dump-config")
+    {
+        let mut definitions: Vec<RuleDefinition> = RULES
+            .iter()
+            .map(|(name, rule)| RuleDefinition::new(name, rule))
+            .collect();
+        if let Some(file) = matches.get_one::<String>("rules-file") {
+            definitions.extend(
+                RuleDefinition::load_file(Path::new(file))
+                    .with_context(|| format!("Invalid rules file '{file}'"))?,
+            );
+        }
+        let mut usage = RuleUsage::load();
+        if matches.get_flag("sort-rules-by-usage") {
+            definitions.sort_by_key(|v| std::cmp::Reverse(usage.count(&v.name)));
+        }
+        let selected = select_rules(&definitions)?;
+        usage.record(selected.iter().map(|v| v.name.as_str()));
+        // failing to remember the selection should not stop the search
+        let _ = usage.save();
+        for definition in selected {
+            config.add_rule_definition(&definition)?;
+        }
+    }
+
+    config.exclude = profile
+        .exclude
+        .iter()
+        .chain(matches.get_many::<String>("exclude").into_iter().flatten())
+        .map(|v| expand_path(v))
+        .collect();
+    if config.exclude.is_empty() {
+        if let Some(ConfigValue::List(exclude)) = settings.option("exclude") {
+            config.exclude = exclude.clone();
+        }
+    }
+    if matches.get_flag("exclude-git") && !config.exclude.iter().any(|v| v == ".git") {
+        config.exclude.push(".git".into());
+    }
 
-    config.exclude = matches
-        .get_many::<String>("exclude")
-        .map(|v| v.cloned().collect())
+    config.exclude_paths = matches
+        .get_many::<String>("exclude-path")
+        .map(|v| {
+            v.map(|v| expand_path(v))
+                .map(|v| std::fs::canonicalize(&v).unwrap_or_else(|_| PathBuf::from(v)))
+                .collect()
+        })
         .unwrap_or_default();
 
+    config.strip_prefix = matches
+        .get_one::<String>("strip-prefix")
+        .map(|v| expand_path(v))
+        .map(|v| std::fs::canonicalize(&v).unwrap_or_else(|_| PathBuf::from(v)));
+
+    config.no_wrap = matches.get_flag("no-wrap");
+    config.mouse = matches.get_flag("mouse");
+    config.unknown = matches.get_flag("unknown");
+    config.atime = matches.get_flag("atime");
+    config.per_project = matches.get_flag("per-project");
+    config.age_color = matches.get_flag("age-color");
+    config.group_deleted = matches.get_flag("group-deleted");
+    config.sorted_output = matches.get_flag("sorted-output");
+    config.yes = matches.get_flag("yes");
+    config.fail_log = matches.get_one::<String>("fail-log").map(PathBuf::from);
+    config.save_session = matches.get_one::<String>("save-session").map(PathBuf::from);
+    config.load_session = matches.get_one::<String>("load-session").map(PathBuf::from);
+    config.format = matches.get_one::<String>("format").cloned();
+    config.relative = matches.get_flag("relative");
+    config.fs_info = matches.get_flag("fs-info");
+    config.no_hardlink_dedup = matches.get_flag("no-hardlink-dedup");
+    // `--size` needs the sizes, so only `--no-size` overrides it
+    config.no_size = matches.get_flag("no-size")
+        || (settings.compute_size == Some(false)
+            && !matches.contains_id("size")
+            && profile.size.is_none());
+    // without a TUI the targets are listed by the prompt, which needs their sizes
+    config.lazy_size = matches.get_flag("lazy-size") && is_tui_supported();
+    config.recurse_into_matches = matches.get_flag("recurse-into-matches");
+    config.empty_dirs = matches.get_flag("empty-dirs");
+    config.header = matches.get_flag("header");
+    config.verbose = matches.get_flag("verbose");
+    config.delete_symlinks = matches.get_flag("delete-symlinks");
+    config.check_in_use = matches.get_flag("check-in-use");
+    if config.check_in_use && !cfg!(target_os = "linux") {
+        eprintln!("--check-in-use is not supported on this platform, ignored");
+        config.check_in_use = false;
+    }
+    config.watch = matches.get_flag("watch");
+    config.include_archived = matches.get_flag("include-archived");
+
     if let Some(time) = matches.get_one::<String>("time") {
         config.set_time(time)?;
+    } else if let Some(time) = &profile.time {
+        config.set_time(time)?;
+    }
+
+    if let Some(minutes) = matches.get_one::<String>("skip-recent") {
+        config.set_skip_recent(minutes)?;
+    }
+
+    if let Some(tolerance) = matches.get_one::<String>("size-tolerance") {
+        config.set_size_tolerance(tolerance)?;
     }
 
     if let Some(size) = matches.get_one::<String>("size") {
         config.set_size(size)?;
+        if !size.starts_with(['+', '-', '=']) && config.size_tolerance.is_none() {
+            eprintln!(
+                "warning: --size {size} only matches targets of exactly {size}, use +{size} for larger ones, --size-tolerance for about {size} or ={size} to silence this warning"
+            );
+        }
+    } else if let Some(size) = profile
+        .size
+        .as_ref()
+        .filter(|_| !config.no_size && !config.lazy_size)
+    {
+        config.set_size(size)?;
+    }
+
+    if let Some(min_files) = matches.get_one::<String>("min-files") {
+        config.set_min_files(min_files)?;
+    }
+
+    if let Some(separator) = matches.get_one::<String>("separator") {
+        config.set_separator(separator)?;
+    }
+
+    if let Some(percent) = matches.get_one::<String>("size-percent") {
+        config.set_size_percent(percent)?;
     }
 
-    for rule in rules {
-        config.add_rule(&rule)?;
+    if let Some(keep_recent) = matches.get_one::<String>("keep-recent") {
+        config.set_keep_recent(keep_recent)?;
     }
 
+    if let Some(timeout) = matches.get_one::<String>("timeout") {
+        config.set_timeout(timeout)?;
+    }
+
+    if let Some(units) = matches.get_one::<String>("units") {
+        config.set_units(units)?;
+    } else if matches.get_flag("bytes") {
+        config.set_units("bytes")?;
+    }
+
+    if let Some(color) = matches.get_one::<String>("color") {
+        config.set_color(color)?;
+    }
+
+    if let Some(top) = matches.get_one::<String>("top") {
+        config.set_top(top)?;
+    }
+
+    if let Some(max_items) = matches.get_one::<String>("max-items") {
+        config.set_max_items(max_items)?;
+    }
+
+    if let Some(max_results) = matches.get_one::<String>("max-results") {
+        config.set_max_results(max_results)?;
+    }
+
+    if let Some(backend) = matches.get_one::<String>("delete-backend") {
+        config.set_delete_backend(backend)?;
+    }
+
+    if let Some(truncate) = matches.get_one::<String>("truncate") {
+        config.set_truncate(truncate)?;
+    }
+
+    if let Some(order) = matches.get_one::<String>("delete-order") {
+        config.set_delete_order(order)?;
+    }
+
+    if let Some(free) = matches.get_one::<String>("free") {
+        config.set_free(free)?;
+    }
+
+    let archives = settings
+        .archives
+        .iter()
+        .chain(matches.get_many::<String>("archive").into_iter().flatten());
+    for archive in archives {
+        config.add_archive(archive)?;
+    }
+
+    apply_settings_options(&mut config, matches, settings, &profile)?;
+
     Ok(config)
 }
 
-fn set_working_dir(matches: &clap::ArgMatches) -> Result<PathBuf> {
-    if let Some(current_dir) = matches.get_one::<String>("cwd") {
-        let current_dir = Path::new(current_dir);
+/// apply the options of a config file written by `--dump-config`, the command line and the
+/// profile take precedence
+fn apply_settings_options(
+    config: &mut Config,
+    matches: &clap::ArgMatches,
+    settings: &Settings,
+    profile: &Profile,
+) -> Result<()> {
+    let from_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    for (key, value) in &settings.options {
+        let id = match key.as_str() {
+            // applied with the other sources of rules and excludes
+            "rules" | "exclude" => continue,
+            "exclude_paths" => "exclude-path".to_string(),
+            "archives" => "archive".to_string(),
+            key => key.replace('_', "-"),
+        };
+        let skip = from_command_line(&id)
+            || match key.as_str() {
+                "time" => profile.time.is_some(),
+                "size" => profile.size.is_some() || config.no_size || config.lazy_size,
+                "units" => from_command_line("bytes"),
+                "no_size" => from_command_line("size"),
+                "lazy_size" => !is_tui_supported(),
+                "check_in_use" => !cfg!(target_os = "linux"),
+                _ => false,
+            };
+        // a dumped flag is false unless it was set, which must not clear the other sources
+        if skip || matches!(value, ConfigValue::Bool(false)) {
+            continue;
+        }
+        config
+            .set_entry(key, value.clone())
+            .with_context(|| format!("Invalid config file option '{key}'"))?;
+    }
+    Ok(())
+}
 
-        if !is_existing_directory(current_dir) {
-            return Err(anyhow!(
-                "The '--file' path '{}' is not a directory.",
-                current_dir.to_string_lossy()
-            ));
+fn set_working_dir(matches: &clap::ArgMatches, settings: &Settings) -> Result<PathBuf> {
+    let current_dir = match (matches.get_one::<String>("cwd"), &settings.default_root) {
+        (Some(current_dir), _) => PathBuf::from(expand_path(current_dir)),
+        (None, Some(default_root)) if !is_project_tree(&env::current_dir()?) => {
+            default_root.clone()
         }
-        let base_directory = canonicalize(current_dir).unwrap();
-        env::set_current_dir(&base_directory).with_context(|| {
-            format!(
-                "Cannot set '{}' as the current working directory",
-                base_directory.to_string_lossy()
-            )
-        })?;
-        Ok(base_directory)
+        _ => PathBuf::from("."),
+    };
+    let current_dir = current_dir.as_path();
+
+    if !is_existing_directory(current_dir) {
+        return Err(anyhow!(
+            "The '--file' path '{}' is not a directory.",
+            current_dir.to_string_lossy()
+        ));
+    }
+    let base_directory = canonicalize(current_dir)
+        .with_context(|| format!("Cannot resolve '{}'", current_dir.to_string_lossy()))?;
+    env::set_current_dir(&base_directory).with_context(|| {
+        format!(
+            "Cannot set '{}' as the current working directory",
+            base_directory.to_string_lossy()
+        )
+    })?;
+    if matches.get_flag("logical-paths") {
+        // fall back to the resolved path if `$PWD` is stale
+        if let Some(path) = logical_path(current_dir)
+            .filter(|v| canonicalize(v).ok().as_ref() == Some(&base_directory))
+        {
+            return Ok(path);
+        }
+    }
+    Ok(base_directory)
+}
+
+fn is_glob(value: &str) -> bool {
+    value.contains(['*', '?', '['])
+}
+
+/// search roots matched by a `--cwd` glob, each target is relative to its own root
+fn glob_roots(pattern: &str) -> Result<Vec<PathBuf>> {
+    let pattern = expand_path(pattern);
+    let paths =
+        glob::glob(&pattern).with_context(|| format!("Invalid '--cwd' glob '{pattern}'"))?;
+    let mut roots = vec![];
+    for path in paths.flatten().filter(|v| is_existing_directory(v)) {
+        roots.push(
+            canonicalize(&path)
+                .with_context(|| format!("Cannot resolve '{}'", path.to_string_lossy()))?,
+        );
+    }
+    if roots.is_empty() {
+        bail!("The '--cwd' glob '{pattern}' matches no directories");
+    }
+    roots.sort();
+    roots.dedup();
+    Ok(roots)
+}
+
+/// absolute path with its parent resolved, the last component is kept as is
+fn resolve_path(path: &str) -> Result<PathBuf> {
+    let path = Path::new(path);
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid path '{}'", path.display()))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent =
+        canonicalize(parent).with_context(|| format!("Cannot resolve '{}'", path.display()))?;
+    Ok(parent.join(name))
+}
+
+/// absolute path without resolving symlinks, relative paths are joined with `$PWD`
+fn logical_path(path: &Path) -> Option<PathBuf> {
+    let base = if path.is_absolute() {
+        PathBuf::new()
     } else {
-        let current_dir = env::current_dir()?;
-        Ok(current_dir)
+        PathBuf::from(env::var("PWD").ok()?)
+    };
+    let mut output = base;
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                output.pop();
+            }
+            component => output.push(component),
+        }
     }
+    output.is_dir().then_some(output)
 }
 
-fn select_rules() -> Result<Vec<String>> {
-    let options = RULES
-        .map(|(name, rule)| format!("{name:<16}{rule}"))
-        .to_vec();
+fn select_rules(definitions: &[RuleDefinition]) -> Result<Vec<RuleDefinition>> {
+    let options: Vec<String> = definitions
+        .iter()
+        .map(|v| match &v.description {
+            Some(description) => format!("{:<16}{}  {}", v.name, v.rule, description),
+            None => format!("{:<16}{}", v.name, v.rule),
+        })
+        .collect();
 
-    let to_rules = |selections: &[String]| {
+    let to_definitions = |selections: &[String]| {
         selections
             .iter()
-            .map(|sel| {
+            .filter_map(|sel| {
                 options
                     .iter()
-                    .enumerate()
-                    .find(|(_, v)| sel == *v)
-                    .map(|(i, _)| RULES[i].1.to_string())
-                    .unwrap()
+                    .position(|v| sel == v)
+                    .map(|i| definitions[i].clone())
             })
-            .collect::<Vec<String>>()
+            .collect::<Vec<RuleDefinition>>()
     };
 
     let formatter: MultiOptionFormatter<String> = &|a| {
-        to_rules(
+        to_definitions(
             &a.iter()
                 .map(|v| v.value.to_string())
                 .collect::<Vec<String>>(),
         )
+        .iter()
+        .map(|v| v.rule.as_str())
+        .collect::<Vec<&str>>()
         .join(" ")
     };
 
@@ -239,7 +1180,59 @@ fn select_rules() -> Result<Vec<String>> {
     if selections.is_empty() {
         bail!("You did not select any rule :(")
     }
-    Ok(to_rules(&selections))
+    Ok(to_definitions(&selections))
+}
+
+/// lower the priority of the process, threads spawned afterwards inherit it
+#[cfg(unix)]
+fn lower_priority() {
+    // SAFETY: only changes the scheduling priority of the current process
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } != 0 {
+        eprintln!("Failed to lower the process priority");
+    }
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        // SAFETY: ioprio_set takes plain integers and only affects the current process
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            )
+        };
+        if ret != 0 {
+            eprintln!("Failed to lower the process IO priority");
+        }
+    }
+}
+
+#[cfg(windows)]
+fn lower_priority() {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, SetPriorityClass, IDLE_PRIORITY_CLASS,
+    };
+    // SAFETY: the pseudo handle of the current process is always valid
+    if unsafe { SetPriorityClass(GetCurrentProcess(), IDLE_PRIORITY_CLASS) } == 0 {
+        eprintln!("Failed to lower the process priority");
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lower_priority() {
+    eprintln!("--low-priority is not supported on this platform, ignored");
+}
+
+fn is_tui_supported() -> bool {
+    stdout().is_terminal() && env::var("TERM").map(|v| v != "dumb").unwrap_or(true)
+}
+
+/// whether the path is inside a git repository
+fn is_project_tree(path: &Path) -> bool {
+    path.ancestors().any(|v| v.join(".git").exists())
 }
 
 fn is_existing_directory(path: &Path) -> bool {
@@ -0,0 +1,330 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+
+use crate::error::ProjcleanError;
+use crate::{Config, ConfigValue};
+
+/// settings loaded from the config file
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub default_root: Option<PathBuf>,
+    /// archive globs, see `--archive`
+    pub archives: Vec<String>,
+    /// compute the sizes of targets, see `--no-size`
+    pub compute_size: Option<bool>,
+    /// named sets of options from `[profile.<name>]` sections, see `--profile`
+    pub profiles: HashMap<String, Profile>,
+    /// options as dumped by `--dump-config`, the command line ones take precedence
+    pub options: Vec<(String, ConfigValue)>,
+}
+
+/// options of a profile, the command line ones take precedence
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub rules: Vec<String>,
+    pub exclude: Vec<String>,
+    pub time: Option<String>,
+    pub size: Option<String>,
+}
+
+impl Settings {
+    /// load settings from `$PROJCLEAN_CONFIG_FILE` or `<config dir>/projclean/config`
+    pub fn load() -> crate::error::Result<Self> {
+        let Some(path) = config_file_path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        content.parse::<Self>().map_err(|err| {
+            ProjcleanError::InvalidConfig(format!(
+                "Invalid config file '{}', {err}",
+                path.display()
+            ))
+        })
+    }
+
+    /// the value of an option dumped by `--dump-config`
+    pub fn option(&self, key: &str) -> Option<&ConfigValue> {
+        self.options.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// the named profile, or `default` if defined when no name is given
+    pub fn profile(&self, name: Option<&str>) -> Result<Option<&Profile>> {
+        let Some(name) = name else {
+            return Ok(self.profiles.get("default"));
+        };
+        match self.profiles.get(name) {
+            Some(profile) => Ok(Some(profile)),
+            None => {
+                let mut names: Vec<&str> = self.profiles.keys().map(|v| v.as_str()).collect();
+                names.sort_unstable();
+                if names.is_empty() {
+                    bail!("Unknown profile '{}', no profiles are defined", name)
+                }
+                bail!(
+                    "Unknown profile '{}', available profiles: {}",
+                    name,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Settings {
+    type Err = anyhow::Error;
+
+    /// parse the TOML config file, the `[profile.<name>]` sections define profiles and the other
+    /// keys are the options of `--dump-config`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let table: toml::Table = toml::from_str(s).map_err(|err| anyhow!("{}", err.message()))?;
+        let mut settings = Settings::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "default_root" => settings.default_root = Some(string(&key, value)?.into()),
+                "archive" => settings.archives = list(&key, value)?,
+                "compute_size" => {
+                    let toml::Value::Boolean(value) = value else {
+                        bail!("Invalid value of compute_size");
+                    };
+                    settings.compute_size = Some(value);
+                }
+                "profile" => {
+                    let toml::Value::Table(profiles) = value else {
+                        bail!("Invalid value of profile");
+                    };
+                    for (name, profile) in profiles {
+                        let toml::Value::Table(profile) = profile else {
+                            bail!("Invalid profile '{}'", name);
+                        };
+                        let profile = parse_profile(&name, profile)?;
+                        settings.profiles.insert(name, profile);
+                    }
+                }
+                _ => {
+                    if value.is_table() {
+                        bail!("Unknown section '[{}]'", key);
+                    }
+                    let value = ConfigValue::from_toml(value)
+                        .with_context(|| format!("Invalid value of {}", key))?;
+                    // checked now so that a broken file is reported even if the option is unused
+                    Config::default().set_entry(&key, value.clone())?;
+                    settings.options.push((key, value));
+                }
+            }
+        }
+        Ok(settings)
+    }
+}
+
+fn parse_profile(name: &str, table: toml::Table) -> Result<Profile> {
+    let mut profile = Profile::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "rules" => profile.rules = list(&key, value)?,
+            "exclude" => profile.exclude = list(&key, value)?,
+            "time" => profile.time = Some(string(&key, value)?),
+            "size" => profile.size = Some(string(&key, value)?),
+            _ => bail!("Unknown key '{}' in profile '{}'", key, name),
+        }
+    }
+    Ok(profile)
+}
+
+/// a string, or a number written without quotes
+fn string(key: &str, value: toml::Value) -> Result<String> {
+    match ConfigValue::from_toml(value) {
+        Some(ConfigValue::Str(value)) => Ok(value),
+        Some(ConfigValue::Int(value)) => Ok(value.to_string()),
+        _ => bail!("Invalid value of {}", key),
+    }
+}
+
+/// a list of strings, a single string is a list of one
+fn list(key: &str, value: toml::Value) -> Result<Vec<String>> {
+    match ConfigValue::from_toml(value) {
+        Some(ConfigValue::List(values)) => Ok(values),
+        Some(ConfigValue::Str(value)) => Ok(vec![value]),
+        _ => bail!("Invalid value of {}", key),
+    }
+}
+
+/// how often each rule was selected in the rule picker, kept in the state file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleUsage {
+    counts: HashMap<String, u64>,
+}
+
+impl RuleUsage {
+    /// load from `$PROJCLEAN_STATE_FILE` or `<state dir>/projclean/rule_usage`, a missing or
+    /// broken file counts as no usage
+    pub fn load() -> Self {
+        state_file_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|content| content.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = state_file_path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, self.to_string())
+            .with_context(|| format!("Failed to write state file '{}'", path.display()))
+    }
+
+    pub fn count(&self, name: &str) -> u64 {
+        self.counts.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn record<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        for name in names {
+            *self.counts.entry(name.to_string()).or_default() += 1;
+        }
+    }
+}
+
+impl std::str::FromStr for RuleUsage {
+    type Err = anyhow::Error;
+
+    /// parse `<count> <name>` lines
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut counts = HashMap::new();
+        for line in s.lines().filter(|v| !v.trim().is_empty()) {
+            let Some((count, name)) = line.split_once(' ') else {
+                bail!("Invalid line '{}'", line);
+            };
+            let count = count
+                .parse()
+                .with_context(|| format!("Invalid line '{}'", line))?;
+            counts.insert(name.to_string(), count);
+        }
+        Ok(Self { counts })
+    }
+}
+
+impl std::fmt::Display for RuleUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut counts: Vec<_> = self.counts.iter().collect();
+        counts.sort();
+        for (name, count) in counts {
+            writeln!(f, "{count} {name}")?;
+        }
+        Ok(())
+    }
+}
+
+fn state_file_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var("PROJCLEAN_STATE_FILE") {
+        return Some(PathBuf::from(path));
+    }
+    let state_dir = env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|_| env::var("HOME").map(|v| PathBuf::from(v).join(".local").join("state")))
+        .ok()?;
+    Some(state_dir.join("projclean").join("rule_usage"))
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var("PROJCLEAN_CONFIG_FILE") {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("APPDATA").map(PathBuf::from))
+        .or_else(|_| env::var("HOME").map(|v| PathBuf::from(v).join(".config")))
+        .ok()?;
+    Some(config_dir.join("projclean").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        let settings: Settings = "# comment\n\ndefault_root = \"/code\"\n".parse().unwrap();
+        assert_eq!(settings.default_root, Some(PathBuf::from("/code")));
+        let settings: Settings = "archive = [\"archive\", \"*/old\"]".parse().unwrap();
+        assert_eq!(settings.archives, vec!["archive", "*/old"]);
+        let settings: Settings = "archive = \"archive\"".parse().unwrap();
+        assert_eq!(settings.archives, vec!["archive"]);
+        assert!("default_root".parse::<Settings>().is_err());
+        assert!("default_root = /code".parse::<Settings>().is_err());
+        assert!("foo = \"bar\"".parse::<Settings>().is_err());
+        let settings: Settings = "compute_size = false".parse().unwrap();
+        assert_eq!(settings.compute_size, Some(false));
+        assert!("compute_size = \"no\"".parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn test_parse_dumped_options() {
+        let settings: Settings = "rules = [\"node_modules\"]\ntop = 3\nwatch = true"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            settings.option("rules"),
+            Some(&ConfigValue::List(vec!["node_modules".into()]))
+        );
+        assert_eq!(settings.option("top"), Some(&ConfigValue::Int(3)));
+        assert_eq!(settings.option("watch"), Some(&ConfigValue::Bool(true)));
+        assert!("top = \"x\"".parse::<Settings>().is_err());
+        assert!("watch = 1".parse::<Settings>().is_err());
+        assert!("foo = 1".parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let settings: Settings = r#"
+archive = "archive"
+[profile.default]
+rules = ["node_modules", "target@Cargo.toml"]
+[profile.work]
+rules = ["bin,obj@*.csproj"]
+exclude = ["vendor", ".git"]
+time = "+30"
+size = "+1G"
+"#
+        .parse()
+        .unwrap();
+        assert_eq!(settings.archives, vec!["archive"]);
+        assert_eq!(
+            settings.profile(None).unwrap().unwrap().rules,
+            vec!["node_modules", "target@Cargo.toml"]
+        );
+        assert_eq!(
+            settings.profile(Some("work")).unwrap(),
+            Some(&Profile {
+                rules: vec!["bin,obj@*.csproj".into()],
+                exclude: vec!["vendor".into(), ".git".into()],
+                time: Some("+30".into()),
+                size: Some("+1G".into()),
+            })
+        );
+        let err = settings.profile(Some("home")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile 'home', available profiles: default, work"
+        );
+        assert!("[profile.a]\nfoo = 1".parse::<Settings>().is_err());
+        assert!("[other]".parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn test_rule_usage() {
+        let mut usage: RuleUsage = "3 cargo\n1 my rule\n".parse().unwrap();
+        assert_eq!(usage.count("cargo"), 3);
+        assert_eq!(usage.count("nodejs"), 0);
+        usage.record(["nodejs", "my rule"]);
+        assert_eq!(usage.to_string(), "3 cargo\n2 my rule\n1 nodejs\n");
+        assert!("cargo".parse::<RuleUsage>().is_err());
+    }
+}
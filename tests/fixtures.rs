@@ -4,7 +4,8 @@ use anyhow::Result;
 use assert_cmd::prelude::*;
 use assert_fs::fixture::TempDir;
 use assert_fs::prelude::*;
-use std::process::Command;
+use std::path::Path;
+use std::process::{Command, Output};
 
 pub const PATHS: [&str; 20] = [
     "nodejs/node_modules/",
@@ -30,13 +31,14 @@ pub const PATHS: [&str; 20] = [
 ];
 
 pub fn search(tmpdir: TempDir, rules: &[&str]) -> Result<Vec<String>> {
+    let mut args = vec!["-P"];
+    args.extend_from_slice(rules);
+    output_paths(tmpdir, &args)
+}
+
+pub fn output_paths(tmpdir: TempDir, args: &[&str]) -> Result<Vec<String>> {
     let name = tmpdir.file_name().unwrap().to_string_lossy().to_string();
-    let output = Command::cargo_bin("projclean")
-        .expect("Couldn't find test binary")
-        .current_dir(tmpdir.path())
-        .arg("-P")
-        .args(rules)
-        .output()?;
+    let output = run(tmpdir.path(), args, &[]);
     let output = std::str::from_utf8(&output.stdout)?.trim().to_string();
     let mut paths: Vec<String> = output
         .split('\n')
@@ -53,6 +55,16 @@ pub fn search(tmpdir: TempDir, rules: &[&str]) -> Result<Vec<String>> {
     Ok(paths)
 }
 
+/// run projclean in the dir with extra environment variables
+pub fn run(dir: &Path, args: &[&str], env: &[(&str, &Path)]) -> Output {
+    let mut command = Command::cargo_bin("projclean").expect("Couldn't find test binary");
+    command.current_dir(dir).args(args);
+    for (key, value) in env {
+        command.env(key, value);
+    }
+    command.output().expect("Couldn't run the test binary")
+}
+
 pub fn tmpdir() -> TempDir {
     let tmpdir = assert_fs::TempDir::new().expect("Couldn't create a temp dir for tests");
     for path in PATHS {
@@ -0,0 +1,630 @@
+use assert_fs::prelude::*;
+use fixtures::{output_paths, run};
+
+use crate::fixtures::tmpdir;
+
+mod fixtures;
+
+#[test]
+fn unknown() {
+    assert_eq!(
+        output_paths(
+            tmpdir(),
+            &["--unknown", "target@Cargo.toml", "node_modules"]
+        )
+        .unwrap(),
+        vec![
+            "cargo-not/target",
+            "gradle-kts/.gradle",
+            "gradle-kts/build",
+            "gradle/.gradle",
+            "gradle/build"
+        ]
+    );
+}
+
+#[test]
+fn timeout() {
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "--timeout", "0", "node_modules"]).unwrap(),
+        vec![""]
+    );
+}
+
+#[test]
+fn top() {
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "--top", "0", "node_modules"]).unwrap(),
+        vec!["and 1 more totaling 0"]
+    );
+}
+
+#[test]
+fn delete_all_sorted_output() {
+    let dir = tmpdir();
+    let args = ["-D", "-y", "--sorted-output", ".gradle,build@build.gradle"];
+    let stdout = String::from_utf8(run(dir.path(), &args, &[]).stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    // each target is listed before the confirmation, then reported once deleted
+    assert_eq!(lines.len(), 5);
+    assert!(lines[..2].iter().all(|v| v.starts_with("0 ")));
+    assert_eq!(lines[2], "Total: 2 targets, 0");
+    assert!(lines[3].starts_with("Delete ") && lines[3].ends_with(".gradle"));
+    assert!(lines[4].starts_with("Delete ") && lines[4].ends_with("build"));
+}
+
+#[test]
+fn relative() {
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "--relative", "node_modules"]).unwrap(),
+        vec!["nodejs/node_modules"]
+    );
+}
+
+#[test]
+fn archive() {
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "--archive", "dotnet-*", "bin"]).unwrap(),
+        vec![""]
+    );
+    assert_eq!(
+        output_paths(
+            tmpdir(),
+            &["-P", "--archive", "dotnet-*", "--include-archived", "bin"]
+        )
+        .unwrap(),
+        vec!["dotnet-cs/bin", "dotnet-fs/bin"]
+    );
+}
+
+#[test]
+fn max_results() {
+    let paths = output_paths(tmpdir(), &["-P", "--max-results", "1", "bin,obj"]).unwrap();
+    assert_eq!(paths.len(), 1);
+    let args = ["-P", "--per-project", "--max-results", "1", "bin,obj"];
+    let paths = output_paths(tmpdir(), &args).unwrap();
+    assert_eq!(paths.len(), 1);
+}
+
+#[test]
+fn test_rule() {
+    assert_eq!(
+        output_paths(
+            tmpdir(),
+            &["--test-rule", "target@Cargo.toml", "--against", "cargo"]
+        )
+        .unwrap(),
+        vec!["detect: Cargo.toml", "purge: target"]
+    );
+    assert_eq!(
+        output_paths(
+            tmpdir(),
+            &["--test-rule", "target@Cargo.toml", "--against", "cargo-not"]
+        )
+        .unwrap(),
+        vec!["detect: not satisfied", "skip: target"]
+    );
+}
+
+#[test]
+fn exclude_path() {
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "--exclude-path", "dotnet-cs", "bin"]).unwrap(),
+        vec!["dotnet-fs/bin"]
+    );
+}
+
+#[test]
+fn exclude_git() {
+    let with_git = || {
+        let dir = tmpdir();
+        dir.child(".git/hooks/node_modules")
+            .create_dir_all()
+            .unwrap();
+        dir.child(".github/node_modules").create_dir_all().unwrap();
+        dir
+    };
+    assert_eq!(
+        output_paths(with_git(), &["-P", "node_modules"]).unwrap(),
+        vec![
+            ".git/hooks/node_modules",
+            ".github/node_modules",
+            "nodejs/node_modules"
+        ]
+    );
+    assert_eq!(
+        output_paths(with_git(), &["-P", "--exclude-git", "node_modules"]).unwrap(),
+        vec![".github/node_modules", "nodejs/node_modules"]
+    );
+}
+
+#[test]
+fn tree() {
+    assert_eq!(
+        output_paths(tmpdir(), &["--tree", "bin"]).unwrap(),
+        vec![
+            "    └── bin 0",
+            ".",
+            "│   └── bin 0",
+            "└── dotnet-fs",
+            "├── dotnet-cs",
+        ]
+    );
+}
+
+#[test]
+fn retry_from() {
+    let dir = tmpdir();
+    dir.child("fail.log")
+        .write_str(&format!(
+            "root\t{}\nnodejs/node_modules\nmissing\n",
+            dir.path().display()
+        ))
+        .unwrap();
+    assert_eq!(
+        output_paths(dir, &["--retry-from", "fail.log"]).unwrap(),
+        vec!["Delete nodejs/node_modules"]
+    );
+}
+
+#[test]
+fn retry_from_outside_root() {
+    let dir = tmpdir();
+    let fail_log = dir.child("fail.log");
+    fail_log
+        .write_str(&format!(
+            "root\t{}\n{}\n",
+            dir.child("cargo").path().display(),
+            dir.child("nodejs/node_modules").path().display()
+        ))
+        .unwrap();
+    let output = run(dir.path(), &["--retry-from", "fail.log"], &[]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("outside the search root"));
+    assert!(dir.child("nodejs/node_modules").path().exists());
+
+    // a fail log without roots is refused as a whole
+    fail_log
+        .write_str(&format!(
+            "{}\n",
+            dir.child("nodejs/node_modules").path().display()
+        ))
+        .unwrap();
+    let output = run(dir.path(), &["--retry-from", "fail.log"], &[]);
+    assert!(!output.status.success());
+    assert!(dir.child("nodejs/node_modules").path().exists());
+}
+
+#[test]
+fn verbose_delete() {
+    let lines = output_paths(tmpdir(), &["-D", "-y", "--verbose", "node_modules"]).unwrap();
+    assert!(lines
+        .iter()
+        .any(|v| v.starts_with("nodejs/node_modules (0) in ") && v.ends_with('s')));
+}
+
+#[test]
+fn cwd_glob() {
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "-C", "dotnet-*", "bin,obj"]).unwrap(),
+        vec![
+            "dotnet-cs/bin",
+            "dotnet-cs/obj",
+            "dotnet-fs/bin",
+            "dotnet-fs/obj"
+        ]
+    );
+    let dir = tmpdir();
+    let output = run(dir.path(), &["-P", "-C", "missing-*", "bin"], &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("matches no directories"));
+}
+
+#[test]
+fn skip_recent() {
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "--skip-recent", "10", "node_modules"]).unwrap(),
+        vec![""]
+    );
+    assert_eq!(
+        output_paths(tmpdir(), &["-P", "--skip-recent", "0", "node_modules"]).unwrap(),
+        vec!["nodejs/node_modules"]
+    );
+}
+
+#[test]
+fn size_percent() {
+    let dir = tmpdir();
+    dir.child("big/cache/a")
+        .write_str(&"a".repeat(100_000))
+        .unwrap();
+    dir.child("small/cache/a").write_str("a").unwrap();
+    assert_eq!(
+        output_paths(dir, &["-P", "--size-percent", "10", "cache"]).unwrap(),
+        vec!["big/cache"]
+    );
+}
+
+#[test]
+fn units() {
+    let dir = tmpdir();
+    dir.child("nodejs/node_modules/a")
+        .write_str(&"a".repeat(2000))
+        .unwrap();
+    let lines = output_paths(dir, &["--histogram", "--units", "si", "node_modules"]).unwrap();
+    assert!(lines
+        .iter()
+        .any(|v| v.starts_with("Total: 1 targets, ") && v.ends_with("kB")));
+}
+
+#[test]
+fn histogram() {
+    let lines = output_paths(tmpdir(), &["--histogram", "bin"]).unwrap();
+    assert_eq!(lines.len(), 6);
+    assert!(lines.contains(&"Total: 2 targets, 0".to_string()));
+    assert!(lines
+        .iter()
+        .any(|v| v.trim_start().starts_with("<1M     2 ████")));
+}
+
+#[test]
+fn format() {
+    assert_eq!(
+        output_paths(
+            tmpdir(),
+            &[
+                "-P",
+                "--format",
+                "{relative_path}:{detect}",
+                "bin@*.csproj,*.fsproj"
+            ]
+        )
+        .unwrap(),
+        vec!["dotnet-cs/bin:App.csproj", "dotnet-fs/bin:App.fsproj"]
+    );
+}
+
+#[test]
+fn prompt() {
+    let dir = tmpdir();
+    let output = assert_cmd::Command::cargo_bin("projclean")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--prompt", "bin@*.csproj"])
+        .write_stdin("1\n")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("1) dotnet-cs/bin"));
+    assert!(stdout.contains("Delete "));
+    assert!(!dir.path().join("dotnet-cs/bin").exists());
+}
+
+#[test]
+fn json_summary() {
+    let dir = tmpdir();
+    let args = ["-D", "-y", "--json-summary", "bin@*.csproj,*.fsproj"];
+    let output = run(dir.path(), &args, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let summary = stdout.lines().last().unwrap();
+    assert!(summary.starts_with(r#"{"found": 2, "bytes": "#));
+    assert!(summary.contains(r#""deleted": 2, "freed": "#));
+    assert!(summary.ends_with(r#""errors": 0}"#));
+}
+
+#[test]
+fn no_size() {
+    let dir = tmpdir();
+    let config_file = dir.child("projclean.conf");
+    config_file.write_str("compute_size = false\n").unwrap();
+    let env = [("PROJCLEAN_CONFIG_FILE", config_file.path())];
+    let stdout = |extra: &[&str]| {
+        let args = [
+            &["-P", "--format", "{relative_path}:{size}", "bin@*.csproj"][..],
+            extra,
+        ]
+        .concat();
+        String::from_utf8(run(dir.path(), &args, &env).stdout).unwrap()
+    };
+    assert_eq!(stdout(&[]).trim(), "dotnet-cs/bin:");
+    assert_eq!(stdout(&["--size", "-1G"]).trim(), "dotnet-cs/bin:0");
+}
+
+#[test]
+fn delete_paths() {
+    let dir = tmpdir();
+    let output = run(dir.path(), &["--delete", "cargo/src"], &[]);
+    assert!(!output.status.success());
+    assert!(dir.path().join("cargo/src").exists());
+    let args = ["--delete", "cargo/target", "nodejs/node_modules"];
+    let output = run(dir.path(), &args, &[]);
+    assert!(output.status.success());
+    assert!(!dir.path().join("cargo/target").exists());
+    assert!(!dir.path().join("nodejs/node_modules").exists());
+    let output = run(
+        dir.path(),
+        &["bin@*.csproj", "--delete", "dotnet-fs/bin"],
+        &[],
+    );
+    assert!(!output.status.success());
+    let output = run(dir.path(), &["--delete", "cargo/src", "--force"], &[]);
+    assert!(output.status.success());
+    assert!(!dir.path().join("cargo/src").exists());
+}
+
+#[test]
+fn min_files() {
+    let dir = tmpdir();
+    for path in ["nodejs/node_modules/a", "nodejs/node_modules/b/c"] {
+        dir.child(path).write_str("").unwrap();
+    }
+    assert_eq!(
+        output_paths(dir, &["-P", "--min-files", "2", "node_modules", "bin"]).unwrap(),
+        vec!["nodejs/node_modules"]
+    );
+}
+
+#[test]
+fn empty_dirs() {
+    let dir = tmpdir();
+    dir.child("nodejs/node_modules/a").write_str("").unwrap();
+    dir.child("cargo/target/debug").create_dir_all().unwrap();
+    assert_eq!(
+        output_paths(
+            dir,
+            &["-P", "--empty-dirs", "node_modules", "target@Cargo.toml"]
+        )
+        .unwrap(),
+        vec!["cargo/target"]
+    );
+}
+
+#[test]
+fn strip_prefix() {
+    let dir = tmpdir();
+    let args = ["-P", "--strip-prefix", "dotnet-cs", "bin@*.csproj,*.fsproj"];
+    let output = run(dir.path(), &args, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    assert_eq!(lines.len(), 2);
+    // paths outside the prefix are printed in full
+    assert!(std::path::Path::new(lines[0]).is_absolute());
+    assert!(lines[0].replace('\\', "/").ends_with("dotnet-fs/bin"));
+    assert_eq!(lines[1], "bin");
+}
+
+#[test]
+fn protect_root() {
+    let dir = tmpdir();
+    let fail_log = dir.child("fail.log");
+    fail_log
+        .write_str(&format!(
+            "root\t{}\n{}\n",
+            dir.path().display(),
+            dir.child("cargo").path().display()
+        ))
+        .unwrap();
+    let args = ["--retry-from", fail_log.path().to_str().unwrap()];
+    let output = run(dir.child("cargo/src").path(), &args, &[]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("refusing to delete"));
+    assert!(dir.child("cargo/src").path().exists());
+}
+
+#[test]
+fn exact_size() {
+    let search = |size: &str| {
+        let dir = tmpdir();
+        let output = run(dir.path(), &["-P", "--size", size, "bin@*.csproj"], &[]);
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let (stdout, stderr) = search("0");
+    assert!(stdout.contains("dotnet-cs"));
+    assert!(stderr.contains("only matches targets of exactly 0"));
+    let (stdout, stderr) = search("=0");
+    assert!(stdout.contains("dotnet-cs"));
+    assert!(stderr.is_empty());
+    let (stdout, _) = search("+0");
+    assert!(stdout.is_empty());
+}
+
+#[test]
+fn profile() {
+    let dir = tmpdir();
+    let config_file = dir.child("projclean.conf");
+    config_file
+        .write_str(
+            "[profile.default]\nrules = [\"bin@*.csproj\"]\n[profile.rust]\nrules = [\"target@Cargo.toml\"]\n",
+        )
+        .unwrap();
+    let env = [("PROJCLEAN_CONFIG_FILE", config_file.path())];
+    let run_profile = |extra: &[&str]| {
+        let args = [&["-P", "--format", "{relative_path}"][..], extra].concat();
+        run(dir.path(), &args, &env)
+    };
+    let stdout = |args: &[&str]| String::from_utf8(run_profile(args).stdout).unwrap();
+    assert_eq!(stdout(&[]).trim(), "dotnet-cs/bin");
+    assert_eq!(stdout(&["--profile", "rust"]).trim(), "cargo/target");
+    assert_eq!(
+        stdout(&["--profile", "rust", "node_modules"]).trim(),
+        "nodejs/node_modules"
+    );
+    let output = run_profile(&["--profile", "home"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Unknown profile 'home', available profiles: default, rust"));
+}
+
+#[test]
+fn keep_recent() {
+    let dir = tmpdir();
+    let week_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(7 * 86400);
+    std::fs::File::open(dir.child("dotnet-fs/bin").path())
+        .unwrap()
+        .set_modified(week_ago)
+        .unwrap();
+    assert_eq!(
+        output_paths(dir, &["-P", "--keep-recent", "1", "bin@*.csproj,*.fsproj"]).unwrap(),
+        vec!["dotnet-fs/bin"]
+    );
+    assert_eq!(
+        output_paths(
+            tmpdir(),
+            &["-P", "--keep-recent", "0", "bin@*.csproj,*.fsproj"]
+        )
+        .unwrap(),
+        vec!["dotnet-cs/bin", "dotnet-fs/bin"]
+    );
+}
+
+#[test]
+fn recurse_into_matches() {
+    let nested = || {
+        let dir = tmpdir();
+        dir.child("nodejs/node_modules/native/Cargo.toml")
+            .write_str("")
+            .unwrap();
+        dir.child("nodejs/node_modules/native/target")
+            .create_dir_all()
+            .unwrap();
+        dir.child("nodejs/node_modules/pkg/node_modules")
+            .create_dir_all()
+            .unwrap();
+        dir
+    };
+    let rules = ["node_modules", "target@Cargo.toml"];
+    let args = |extra: &[&'static str]| [&["-P"], extra, &rules[..]].concat();
+    assert_eq!(
+        output_paths(nested(), &args(&[])).unwrap(),
+        vec!["cargo/target", "nodejs/node_modules"]
+    );
+    assert_eq!(
+        output_paths(nested(), &args(&["--recurse-into-matches"])).unwrap(),
+        vec![
+            "cargo/target",
+            "nodejs/node_modules",
+            "nodejs/node_modules/native/target"
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn delete_symlinks() {
+    let dir = tmpdir();
+    let real = dir.child("shared/node_modules");
+    real.create_dir_all().unwrap();
+    dir.child("linked").create_dir_all().unwrap();
+    std::os::unix::fs::symlink(real.path(), dir.child("linked/node_modules").path()).unwrap();
+    let delete = |extra: &[&str]| {
+        let args = [&["-C", "linked", "-D", "-y", "node_modules"][..], extra].concat();
+        run(dir.path(), &args, &[])
+    };
+    let output = delete(&[]);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("it is a symlink"));
+    assert!(dir.child("linked/node_modules").path().is_symlink());
+    delete(&["--delete-symlinks"]);
+    assert!(!dir.child("linked/node_modules").path().is_symlink());
+    assert!(real.path().exists());
+}
+
+#[test]
+fn stats() {
+    let dir = tmpdir();
+    let args = ["-P", "--stats", "node_modules", "target@Cargo.toml"];
+    let output = run(dir.path(), &args, &[]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("dirs visited: "));
+    assert!(stderr.contains("computing sizes: "));
+    assert!(stderr.contains("\n  node_modules: 1\n"));
+    assert!(stderr.contains("\n  target@Cargo.toml: 1\n"));
+}
+
+#[test]
+fn explain() {
+    let dir = tmpdir();
+    let explain = |extra: &[&str]| {
+        let args = [&["--explain"][..], extra].concat();
+        String::from_utf8(run(dir.path(), &args, &[]).stdout).unwrap()
+    };
+    assert_eq!(
+        explain(&["cargo/target", "node_modules", "target@Cargo.toml"]),
+        "node_modules: 'target' is not a target\ntarget@Cargo.toml: matched, detect: Cargo.toml\nresult: matched by target@Cargo.toml\n"
+    );
+    assert!(explain(&["cargo-not/target", "target@Cargo.toml"]).ends_with("result: not matched\n"));
+    assert!(
+        explain(&["cargo/target", "-x", "cargo", "target@Cargo.toml"])
+            .ends_with("result: not searched, inside 'cargo', excluded by --exclude\n")
+    );
+    assert!(
+        explain(&["cargo/target", "--size", "+1G", "target@Cargo.toml"]).ends_with(
+            "result: matched by target@Cargo.toml, but its size of 0 fails the size filter\n"
+        )
+    );
+}
+
+#[test]
+fn header_separator() {
+    let dir = tmpdir();
+    let args = [
+        "-P",
+        "--relative",
+        "--header",
+        "--separator",
+        "\\0",
+        "node_modules",
+        "target@Cargo.toml",
+    ];
+    let output = run(dir.path(), &args, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let (header, results) = stdout.split_once('\n').unwrap();
+    assert!(header.starts_with("# projclean "));
+    assert!(header.ends_with(" UTC"));
+    let mut results: Vec<&str> = results.split_terminator('\0').collect();
+    results.sort();
+    assert_eq!(results, vec!["cargo/target", "nodejs/node_modules"]);
+}
+
+#[test]
+fn filter() {
+    let dir = tmpdir();
+    let output = assert_cmd::Command::cargo_bin("projclean")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--filter", "node_modules", "target@Cargo.toml"])
+        .write_stdin(
+            "cargo/target/\ncargo-not/target\ncargo/src\n./nodejs/node_modules\nmissing/x\n",
+        )
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "cargo/target/\n./nodejs/node_modules\n"
+    );
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+#[test]
+fn trash_without_home() {
+    let dir = tmpdir();
+    let output = assert_cmd::Command::cargo_bin("projclean")
+        .unwrap()
+        .current_dir(dir.path())
+        .env_remove("HOME")
+        .env_remove("XDG_DATA_HOME")
+        .args(["-D", "-y", "--delete-backend", "trash", "node_modules"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("no trash dir"));
+    assert!(dir.path().join("nodejs/node_modules").exists());
+    assert!(!dir.path().join("Trash").exists());
+}
@@ -1,3 +1,4 @@
+use assert_fs::prelude::*;
 use fixtures::search;
 
 use crate::fixtures::tmpdir;
@@ -20,6 +21,14 @@ fn no_detect() {
     );
 }
 
+#[test]
+fn duplicate_rules() {
+    assert_eq!(
+        search(tmpdir(), &["node_modules", "node_modules"]).unwrap(),
+        vec!["nodejs/node_modules"]
+    );
+}
+
 #[test]
 fn multiple_detects() {
     assert_eq!(
@@ -53,3 +62,122 @@ fn mixed() {
         vec!["mixed/_build"]
     );
 }
+
+#[test]
+fn max_depth() {
+    assert_eq!(
+        search(tmpdir(), &["node_modules:2"]).unwrap(),
+        vec!["nodejs/node_modules"]
+    );
+    assert_eq!(search(tmpdir(), &["node_modules:1"]).unwrap(), vec![""]);
+}
+
+#[test]
+fn file_targets() {
+    assert_eq!(
+        search(tmpdir(), &["*.toml,build.gradle"]).unwrap(),
+        vec!["cargo/Cargo.toml", "gradle/build.gradle"]
+    );
+}
+
+#[test]
+fn detect_content() {
+    let dir = tmpdir();
+    dir.child("cargo/Cargo.toml")
+        .write_str("[package]\nname = \"app\"\n")
+        .unwrap();
+    assert_eq!(
+        search(dir, &["target@Cargo.toml:[package]"]).unwrap(),
+        vec!["cargo/target"]
+    );
+    assert_eq!(
+        search(tmpdir(), &["target@Cargo.toml:[package]"]).unwrap(),
+        vec![""]
+    );
+}
+
+#[test]
+fn nested_purge() {
+    let dir = tmpdir();
+    for path in [
+        "sbt/build.sbt",
+        "sbt/target/a",
+        "sbt/project/target/a",
+        "sbt/project/b",
+    ] {
+        dir.child(path).write_str("").unwrap();
+    }
+    assert_eq!(
+        search(dir, &["target,project/target@build.sbt"]).unwrap(),
+        vec!["sbt/project/target", "sbt/target"]
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tmpdir();
+    let project = dir.path().join(OsStr::from_bytes(b"bad\xff"));
+    std::fs::create_dir_all(project.join("bin")).unwrap();
+    std::fs::write(project.join(OsStr::from_bytes(b"\xfe.csproj")), "").unwrap();
+    let cache = project.join(OsStr::from_bytes(b"\xfd.pyc"));
+    std::fs::write(&cache, "").unwrap();
+    assert_eq!(
+        search(dir, &["bin@*.csproj", "*.pyc"]).unwrap(),
+        vec![
+            "bad\u{fffd}/bin",
+            "bad\u{fffd}/\u{fffd}.pyc",
+            "dotnet-cs/bin"
+        ]
+    );
+
+    let dir = tmpdir();
+    let cache = dir.path().join(OsStr::from_bytes(b"\xfd.pyc"));
+    std::fs::write(&cache, "").unwrap();
+    assert_cmd::Command::cargo_bin("projclean")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["-D", "-y", "*.pyc"])
+        .assert()
+        .success();
+    assert!(!cache.exists());
+}
+
+#[test]
+fn co_required_detect() {
+    let dir = tmpdir();
+    for path in [
+        "web/package.json",
+        "web/dist/a",
+        "web/node_modules/b",
+        "lib/package.json",
+        "lib/dist/a",
+    ] {
+        dir.child(path).write_str("").unwrap();
+    }
+    assert_eq!(
+        search(dir, &["dist@package.json+node_modules"]).unwrap(),
+        vec!["web/dist"]
+    );
+}
+
+#[test]
+fn marker_file() {
+    let dir = tmpdir();
+    for path in [
+        "cache/CACHEDIR.TAG",
+        "cache/data/a",
+        "app/tmp/CACHEDIR.TAG",
+        "app/tmp/nested/CACHEDIR.TAG",
+        "app/src/a",
+    ] {
+        dir.child(path).write_str("").unwrap();
+    }
+    assert_eq!(
+        search(dir, &["@CACHEDIR.TAG"]).unwrap(),
+        vec!["app/tmp", "cache"]
+    );
+}